@@ -0,0 +1,108 @@
+use crate::{Oversampling, ParamId, MAX_DELAY_TIME, MAX_DRIFT};
+
+/// Sets the limits that the buffers are allocated for when a `GrainDelay` is constructed.
+/// Parameter values beyond these limits are clamped, and so are limits beyond the parameter ranges.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrainDelayConfig {
+  /// The maximum delay time in seconds, up to `MAX_DELAY_TIME`.
+  pub max_delay_time: f32,
+  /// The maximum grain pitch in semitones.
+  pub max_pitch: f32,
+  /// The maximum spray in seconds.
+  pub max_spray: f32,
+  /// The minimum grain frequency in Hz.
+  pub min_frequency: f32,
+  pub oversampling: Oversampling,
+  /// The highest sample rate that `set_sample_rate` can switch to without reallocating.
+  pub max_sample_rate: f32,
+}
+
+impl Default for GrainDelayConfig {
+  fn default() -> Self {
+    Self {
+      max_delay_time: 5.,
+      max_pitch: 24.,
+      max_spray: 0.5,
+      min_frequency: 1.,
+      oversampling: Oversampling::Off,
+      max_sample_rate: 48000.,
+    }
+  }
+}
+
+impl GrainDelayConfig {
+  /// Limits each field to the range of its parameter. Taking the max before the min also replaces
+  /// NaN with the lower bound.
+  pub(crate) fn clamped(&self) -> Self {
+    let frequency = ParamId::Frequency.descriptor();
+    Self {
+      max_delay_time: self.max_delay_time.max(0.).min(MAX_DELAY_TIME),
+      max_pitch: self.max_pitch.max(0.).min(ParamId::Pitch.descriptor().max),
+      max_spray: self
+        .max_spray
+        .max(0.)
+        .min(ParamId::Spray.descriptor().max * 0.001),
+      min_frequency: self.min_frequency.max(frequency.min).min(frequency.max),
+      oversampling: self.oversampling,
+      max_sample_rate: self.max_sample_rate.max(0.),
+    }
+  }
+
+  pub(crate) fn max_time(&self) -> f32 {
+    self.max_delay_time * 1000.
+  }
+
+  pub(crate) fn max_speed(&self) -> f32 {
+    (self.max_pitch / 12.).exp2()
+  }
+
+  pub(crate) fn max_spray(&self) -> f32 {
+    self.max_spray * 1000.
+  }
+
+  // Frozen grains can read up to two extra windows behind the write position.
+  pub(crate) fn max_grain_delay_time(&self) -> f32 {
+    ((3. + ((self.max_pitch + MAX_DRIFT) / 12.).exp2()) / self.min_frequency + self.max_spray)
+      .ceil()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::GrainDelayConfig;
+
+  #[test]
+  fn should_size_the_grain_buffer_for_the_default_limits() {
+    assert_eq!(GrainDelayConfig::default().max_grain_delay_time(), 8.);
+  }
+
+  #[test]
+  fn should_shrink_the_grain_buffer_with_tighter_limits() {
+    let config = GrainDelayConfig {
+      max_pitch: 12.,
+      max_spray: 0.1,
+      min_frequency: 4.,
+      ..Default::default()
+    };
+    assert_eq!(config.max_grain_delay_time(), 2.);
+  }
+
+  #[test]
+  fn should_clamp_a_degenerate_config() {
+    let config = GrainDelayConfig {
+      max_delay_time: f32::NAN,
+      max_pitch: -12.,
+      max_spray: f32::INFINITY,
+      min_frequency: 0.,
+      max_sample_rate: -1.,
+      ..Default::default()
+    }
+    .clamped();
+    assert_eq!(config.max_time(), 0.);
+    assert_eq!(config.max_pitch, 0.);
+    assert_eq!(config.max_spray, 0.5);
+    assert_eq!(config.min_frequency, 1.);
+    assert_eq!(config.max_sample_rate, 0.);
+    assert!(config.max_grain_delay_time().is_finite());
+  }
+}
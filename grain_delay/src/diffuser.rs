@@ -0,0 +1,93 @@
+mod allpass;
+use crate::shared::float_ext::FloatExt;
+use allpass::Allpass;
+use std::f32::consts::TAU;
+
+const TIMES: [f32; 4] = [4.77, 3.59, 12.73, 9.3];
+const RIGHT_TIME_FACTOR: f32 = 1.07;
+const MAX_GAIN: f32 = 0.7;
+const MOD_FREQ: f32 = 0.5;
+const MOD_DEPTH: f32 = 0.25;
+
+/// Series allpass stages with slowly modulated delay times, which smear the repeats.
+pub struct Diffuser {
+  stages: Vec<(Allpass, Allpass)>,
+  phase: f32,
+  phase_step: f32,
+}
+
+impl Diffuser {
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      stages: TIMES
+        .iter()
+        .map(|time| {
+          let max_time = time * RIGHT_TIME_FACTOR + MOD_DEPTH;
+          (
+            Allpass::new(sample_rate, max_time),
+            Allpass::new(sample_rate, max_time),
+          )
+        })
+        .collect(),
+      phase: 0.,
+      phase_step: MOD_FREQ / sample_rate,
+    }
+  }
+
+  /// Clears the allpass stages and adapts to the sample rate without reallocating.
+  pub fn reset(&mut self, sample_rate: f32) {
+    self.stages.iter_mut().for_each(|(left, right)| {
+      left.reset(sample_rate);
+      right.reset(sample_rate);
+    });
+    self.phase = 0.;
+    self.phase_step = MOD_FREQ / sample_rate;
+  }
+
+  /// The amount ranges from 0 to 1, where 0 passes the input unchanged.
+  pub fn process(&mut self, input: (f32, f32), amount: f32) -> (f32, f32) {
+    self.phase = (self.phase + self.phase_step).fract();
+    let gain = amount * MAX_GAIN;
+
+    let diffused = self.stages.iter_mut().zip(TIMES).enumerate().fold(
+      input,
+      |(left, right), (i, ((left_allpass, right_allpass), time))| {
+        // Each stage is modulated with its own phase offset
+        let phase = (self.phase + i as f32 / TIMES.len() as f32).fract();
+        let time = time + (phase * TAU).fast_sin() * MOD_DEPTH;
+        (
+          left_allpass.process(left, time, gain),
+          right_allpass.process(right, time * RIGHT_TIME_FACTOR, gain),
+        )
+      },
+    );
+
+    (
+      input.0.mix(diffused.0, amount),
+      input.1.mix(diffused.1, amount),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Diffuser;
+
+  #[test]
+  fn should_pass_the_input_without_diffusion() {
+    let mut diffuser = Diffuser::new(44100.);
+    assert_eq!(diffuser.process((1., -1.), 0.), (1., -1.));
+    assert_eq!(diffuser.process((0.5, 0.25), 0.), (0.5, 0.25));
+  }
+
+  #[test]
+  fn should_smear_an_impulse() {
+    let mut diffuser = Diffuser::new(44100.);
+    let output: Vec<f32> = (0..4410)
+      .map(|i| diffuser.process((if i == 0 { 1. } else { 0. }, 0.), 1.).0)
+      .collect();
+    let spread = output.iter().filter(|x| x.abs() > 0.001).count();
+    assert!(spread > 100);
+    assert!(output.iter().all(|x| x.abs() < 1.));
+  }
+}
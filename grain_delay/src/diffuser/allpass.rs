@@ -0,0 +1,26 @@
+use crate::shared::delay_line::{DelayLine, Interpolation};
+
+/// A Schroeder allpass filter with a fractional delay time in milliseconds.
+pub struct Allpass {
+  delay_line: DelayLine,
+}
+
+impl Allpass {
+  pub fn new(sample_rate: f32, max_time: f32) -> Self {
+    Self {
+      delay_line: DelayLine::new((sample_rate * max_time * 0.001) as usize + 2, sample_rate),
+    }
+  }
+
+  pub fn reset(&mut self, sample_rate: f32) {
+    self.delay_line.set_sample_rate(sample_rate);
+    self.delay_line.reset();
+  }
+
+  pub fn process(&mut self, input: f32, time: f32, gain: f32) -> f32 {
+    let delayed = self.delay_line.read(time, Interpolation::Linear);
+    let feedforward = input + delayed * gain;
+    self.delay_line.write(feedforward);
+    delayed - feedforward * gain
+  }
+}
@@ -0,0 +1,61 @@
+use crate::shared::{envelope_follower::EnvelopeFollower, float_ext::FloatExt};
+
+/// Lowers the wet signal while the input is playing. The wet signal is fully ducked once the input
+/// is twice as loud as the threshold.
+pub struct Ducker {
+  envelope_follower: EnvelopeFollower,
+  threshold: f32,
+  threshold_gain: f32,
+}
+
+impl Ducker {
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      envelope_follower: EnvelopeFollower::new(sample_rate),
+      threshold: 0.,
+      threshold_gain: 1.,
+    }
+  }
+
+  /// The threshold is in decibels, the amount ranges from 0 to 1 and the attack and release times
+  /// are in milliseconds.
+  pub fn process(
+    &mut self,
+    input: (f32, f32),
+    wet: (f32, f32),
+    threshold: f32,
+    amount: f32,
+    attack: f32,
+    release: f32,
+  ) -> (f32, f32) {
+    if threshold != self.threshold {
+      self.threshold = threshold;
+      self.threshold_gain = threshold.dbtoa();
+    }
+
+    let envelope =
+      self
+        .envelope_follower
+        .process(input.0.abs().max(input.1.abs()), attack, release);
+    let reduction = (envelope / self.threshold_gain - 1.).clamp(0., 1.);
+    let gain = 1. - amount * reduction;
+    (wet.0 * gain, wet.1 * gain)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Ducker;
+
+  #[test]
+  fn should_duck_the_wet_signal_above_the_threshold() {
+    let mut ducker = Ducker::new(1000.);
+    let output = ducker.process((0.01, 0.01), (1., 1.), -20., 1., 1., 10.);
+    assert_eq!(output, (1., 1.));
+    let output = (0..20)
+      .map(|_| ducker.process((1., -1.), (1., 1.), -20., 0.5, 1., 10.))
+      .last()
+      .unwrap();
+    assert_eq!(output, (0.5, 0.5));
+  }
+}
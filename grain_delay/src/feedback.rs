@@ -0,0 +1,119 @@
+use crate::{dc_block::DcBlock, saturation::Saturation};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeedbackMode {
+  Mono,
+  Stereo,
+  PingPong,
+}
+
+impl From<i32> for FeedbackMode {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::Stereo,
+      2 => Self::PingPong,
+      _ => Self::Mono,
+    }
+  }
+}
+
+pub struct Feedback {
+  dc_block: (DcBlock, DcBlock),
+}
+
+impl Feedback {
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      dc_block: (DcBlock::new(sample_rate), DcBlock::new(sample_rate)),
+    }
+  }
+
+  /// Returns the signal that is written to the delay line.
+  /// The cross feedback amount only applies to the stereo mode.
+  pub fn process(
+    &mut self,
+    input: (f32, f32),
+    feedback_input: (f32, f32),
+    feedback: f32,
+    cross_feedback: f32,
+    mode: FeedbackMode,
+    saturation: Saturation,
+  ) -> (f32, f32) {
+    match mode {
+      FeedbackMode::Mono => {
+        let mono_input = (feedback_input.0 + feedback_input.1) * 0.5;
+        let feedback_out = self.apply_feedback(mono_input, feedback, saturation);
+        (input.0 + feedback_out, input.1 + feedback_out)
+      }
+      FeedbackMode::Stereo => {
+        let feedback_input = (
+          feedback_input.0 + (feedback_input.1 - feedback_input.0) * cross_feedback,
+          feedback_input.1 + (feedback_input.0 - feedback_input.1) * cross_feedback,
+        );
+        let feedback_out = self.apply_stereo_feedback(feedback_input, feedback, saturation);
+        (input.0 + feedback_out.0, input.1 + feedback_out.1)
+      }
+      FeedbackMode::PingPong => {
+        let mono_input = (input.0 + input.1) * 0.5;
+        let feedback_out =
+          self.apply_stereo_feedback((feedback_input.1, feedback_input.0), feedback, saturation);
+        (mono_input + feedback_out.0, feedback_out.1)
+      }
+    }
+  }
+
+  fn apply_feedback(&mut self, input: f32, feedback: f32, saturation: Saturation) -> f32 {
+    let feedback_out = input * feedback;
+    self.dc_block.0.process(saturation.process(feedback_out))
+  }
+
+  fn apply_stereo_feedback(
+    &mut self,
+    input: (f32, f32),
+    feedback: f32,
+    saturation: Saturation,
+  ) -> (f32, f32) {
+    (
+      self
+        .dc_block
+        .0
+        .process(saturation.process(input.0 * feedback)),
+      self
+        .dc_block
+        .1
+        .process(saturation.process(input.1 * feedback)),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Feedback, FeedbackMode};
+  use crate::saturation::{Saturation, SaturationCurve};
+
+  const CLIP: Saturation = Saturation {
+    curve: SaturationCurve::Clip,
+    drive: 1.,
+  };
+
+  #[test]
+  fn should_route_ping_pong_feedback() {
+    let mut feedback = Feedback::new(44100.);
+    let output = feedback.process((1., 0.), (0., 0.), 1., 0., FeedbackMode::PingPong, CLIP);
+    assert_eq!(output.0, 0.5);
+    assert_eq!(output.1, 0.);
+    let output = feedback.process((0., 0.), (1., 0.), 1., 0., FeedbackMode::PingPong, CLIP);
+    assert_eq!(output.0, 0.);
+    assert_eq!(output.1, 1.);
+  }
+
+  #[test]
+  fn should_cross_stereo_feedback() {
+    let mut feedback = Feedback::new(44100.);
+    let output = feedback.process((0., 0.), (1., 0.), 1., 0., FeedbackMode::Stereo, CLIP);
+    assert_eq!(output, (1., 0.));
+    let mut feedback = Feedback::new(44100.);
+    let output = feedback.process((0., 0.), (1., 0.), 1., 1., FeedbackMode::Stereo, CLIP);
+    assert_eq!(output, (0., 1.));
+  }
+}
@@ -1,65 +1,385 @@
 mod grain;
 mod phasor;
-use crate::{shared::delay_line::DelayLine, MAX_GRAIN_DELAY_TIME};
-use {grain::Grain, phasor::Phasor};
+use crate::{
+  params::{LinearSmooth, Smoother},
+  shared::{delay_line::Interpolation, freeze::Freeze, stereo_delay_line::StereoDelayLine},
+};
+use fastrand::Rng;
+pub use grain::{PanMode, SprayDistribution, WindowShape};
+use {
+  grain::{Grain, GrainContext, Panning, Spray},
+  phasor::Phasor,
+};
 
-const VOICES: usize = 4;
+pub const MIN_VOICES: usize = 1;
+pub const MAX_VOICES: usize = 32;
+pub const DEFAULT_VOICES: usize = 4;
+const GAIN_CORRECTION_FREQUENCY: f32 = 20.;
+// The spray time in milliseconds from which the grains are considered uncorrelated
+const DECORRELATION_TIME: f32 = 20.;
+
+/// The grain settings besides the frequency and the speed.
+#[derive(Clone, Copy)]
+pub struct GrainSettings {
+  pub spray: f32,
+  pub spray_distribution: SprayDistribution,
+  pub drift: f32,
+  pub reverse: f32,
+  pub spread: f32,
+  pub pan_mode: PanMode,
+  pub voices: usize,
+  pub window_shape: WindowShape,
+  pub taper: f32,
+  pub interpolation: Interpolation,
+  pub freeze: bool,
+}
 
 pub struct Grains {
-  grain_delay_line: DelayLine,
+  grain_delay_line: StereoDelayLine,
   phasor: Phasor,
+  rng: Rng,
+  spray: Spray,
+  panning: Panning,
   grains: Vec<Grain>,
-  gain_correction: f32,
+  voices: usize,
+  playing_voices: usize,
+  gain_correction: LinearSmooth,
+  freeze: Freeze,
+  freeze_offset: f32,
+  sample_period: f32,
 }
 
 impl Grains {
-  pub fn new(sample_rate: f32) -> Self {
-    let grains = (0..VOICES).map(|i| Grain::new(sample_rate, i)).collect();
-
-    Self {
-      grain_delay_line: DelayLine::new((sample_rate * MAX_GRAIN_DELAY_TIME) as usize, sample_rate),
+  /// The max grain delay time in seconds sets the length of the grain buffers.
+  pub fn new(sample_rate: f32, max_grain_delay_time: f32, seed: u64) -> Self {
+    let mut grains = Self {
+      grain_delay_line: StereoDelayLine::new(
+        (sample_rate * max_grain_delay_time) as usize,
+        sample_rate,
+      ),
       phasor: Phasor::new(sample_rate),
-      grains,
-      gain_correction: (VOICES as f32 / 2.).recip(),
-    }
+      rng: Rng::with_seed(seed),
+      spray: Spray::new(),
+      panning: Panning::new(),
+      grains: vec![Grain::new(sample_rate); MAX_VOICES],
+      voices: 0,
+      playing_voices: 0,
+      gain_correction: LinearSmooth::new(sample_rate, GAIN_CORRECTION_FREQUENCY),
+      freeze: Freeze::new(sample_rate),
+      freeze_offset: 0.,
+      sample_period: sample_rate.recip() * 1000.,
+    };
+    grains.set_voices(DEFAULT_VOICES);
+    grains
+      .gain_correction
+      .reset(Self::get_gain_correction(DEFAULT_VOICES, 0.));
+    grains
   }
 
   pub fn process(
     &mut self,
-    input: f32,
-    spray: f32,
+    input: (f32, f32),
     freq: f32,
     speed: f32,
-    drift: f32,
-    reverse: f32,
-    spread: f32,
+    settings: &GrainSettings,
   ) -> (f32, f32) {
+    if settings.voices != self.voices {
+      self.set_voices(settings.voices);
+    }
     let phasor = self.phasor.process(freq);
+    self.spray.set(settings.spray, settings.spray_distribution);
+    self.panning.set(settings.spread, settings.pan_mode);
+    let mut context = GrainContext {
+      delay_line: &self.grain_delay_line,
+      rng: &mut self.rng,
+      spray: &mut self.spray,
+      panning: &mut self.panning,
+      is_frozen: self.freeze.is_frozen(),
+      freeze_offset: self.freeze_offset,
+    };
 
-    let grains_out = self
-      .grains
+    let grains_out = self.grains[..self.playing_voices]
       .iter_mut()
-      .map(|grain| {
-        grain.process(
-          &self.grain_delay_line,
-          phasor,
-          freq,
-          speed,
-          spray,
-          drift,
-          reverse,
-          spread,
-        )
-      })
+      .map(|grain| grain.process(&mut context, phasor, freq, speed, settings))
       .fold((0., 0.), |sum, grain_out| {
         (sum.0 + grain_out.0, sum.1 + grain_out.1)
       });
 
-    self.grain_delay_line.write(input);
+    // Retired grains stop being processed once they have finished their window, and until then
+    // they still add to the level
+    while self.playing_voices > self.voices && !self.grains[self.playing_voices - 1].is_active() {
+      self.playing_voices -= 1;
+    }
+    let sounding_voices = if self.playing_voices > self.voices {
+      self.grains[..self.playing_voices]
+        .iter()
+        .filter(|grain| grain.is_active())
+        .count()
+        .max(self.voices)
+    } else {
+      self.voices
+    };
+    self
+      .gain_correction
+      .set_target(Self::get_gain_correction(sounding_voices, settings.spray));
+    self.write(input, settings.freeze);
 
+    let gain_correction = self.gain_correction.next();
     (
-      grains_out.0 * self.gain_correction,
-      grains_out.1 * self.gain_correction,
+      grains_out.0 * gain_correction,
+      grains_out.1 * gain_correction,
     )
   }
+
+  pub fn reseed(&mut self, seed: u64) {
+    self.rng.seed(seed);
+  }
+
+  /// Clears the grain buffers and the grains and adapts to the sample rate without reallocating.
+  pub fn reset(&mut self, sample_rate: f32) {
+    self.grain_delay_line.set_sample_rate(sample_rate);
+    self.grain_delay_line.reset();
+    self.phasor = Phasor::new(sample_rate);
+    self.spray = Spray::new();
+    self.panning = Panning::new();
+    self
+      .grains
+      .iter_mut()
+      .for_each(|grain| *grain = Grain::new(sample_rate));
+    let voices = self.voices;
+    self.voices = 0;
+    self.playing_voices = 0;
+    self.set_voices(voices);
+    self.gain_correction = LinearSmooth::new(sample_rate, GAIN_CORRECTION_FREQUENCY);
+    self
+      .gain_correction
+      .reset(Self::get_gain_correction(self.voices, 0.));
+    self.freeze = Freeze::new(sample_rate);
+    self.freeze_offset = 0.;
+    self.sample_period = sample_rate.recip() * 1000.;
+  }
+
+  fn write(&mut self, input: (f32, f32), freeze: bool) {
+    if let Some(gain) = self.freeze.process(freeze) {
+      self
+        .grain_delay_line
+        .write((input.0 * gain, input.1 * gain));
+      if self.freeze.is_frozen() {
+        // The write position stops here, so the grains that start from now on read relative to it
+        self.freeze_offset = 0.;
+      } else if self.grains[..self.playing_voices]
+        .iter()
+        .any(Grain::is_frozen)
+      {
+        // Grains that started while frozen keep reading relative to the frozen write position
+        self.freeze_offset += self.sample_period;
+      }
+    }
+  }
+
+  fn set_voices(&mut self, voices: usize) {
+    let voices = voices.clamp(MIN_VOICES, MAX_VOICES);
+    let phase_step = (voices as f32).recip();
+    self.grains[..voices]
+      .iter_mut()
+      .enumerate()
+      .for_each(|(i, grain)| {
+        let phase_offset = phase_step * i as f32;
+        // Playing grains move to their new phase offset once their window has finished
+        if grain.is_active() {
+          grain.set_phase_offset(phase_offset);
+        } else {
+          grain.activate(phase_offset);
+        }
+        // The voices are spread evenly from left to right and a single voice stays in the center
+        grain.set_voice_position(if voices > 1 {
+          i as f32 * 2. / (voices - 1) as f32 - 1.
+        } else {
+          0.
+        });
+      });
+    if self.playing_voices > voices {
+      self.grains[voices..self.playing_voices]
+        .iter_mut()
+        .for_each(Grain::retire);
+    }
+    self.voices = voices;
+    self.playing_voices = self.playing_voices.max(voices);
+  }
+
+  // Grains that read nearly the same audio add up in amplitude, while uncorrelated grains add up in
+  // power. The spray decides how far apart the grains read, so it blends from a linear correction
+  // to one that follows the square root of the voice count.
+  fn get_gain_correction(voices: usize, spray: f32) -> f32 {
+    let exponent = 1. - (spray / DECORRELATION_TIME).min(1.) * 0.5;
+    0.5 * (DEFAULT_VOICES as f32 / voices as f32).powf(exponent)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    GrainSettings, Grains, PanMode, SprayDistribution, WindowShape, DEFAULT_VOICES, MAX_VOICES,
+  };
+  use crate::{params::Smoother, shared::delay_line::Interpolation};
+
+  fn settings() -> GrainSettings {
+    GrainSettings {
+      spray: 0.,
+      spray_distribution: SprayDistribution::Uniform,
+      drift: 0.,
+      reverse: 0.,
+      spread: 0.,
+      pan_mode: PanMode::Random,
+      voices: 4,
+      window_shape: WindowShape::Hann,
+      taper: 0.,
+      interpolation: Interpolation::Linear,
+      freeze: false,
+    }
+  }
+
+  #[test]
+  fn should_clamp_voices() {
+    let mut grains = Grains::new(10., 8., 0);
+    assert_eq!(grains.voices, DEFAULT_VOICES);
+    grains.process(
+      (0., 0.),
+      1.,
+      1.,
+      &GrainSettings {
+        voices: 0,
+        ..settings()
+      },
+    );
+    assert_eq!(grains.voices, 1);
+    grains.process(
+      (0., 0.),
+      1.,
+      1.,
+      &GrainSettings {
+        voices: 64,
+        ..settings()
+      },
+    );
+    assert_eq!(grains.voices, MAX_VOICES);
+  }
+
+  #[test]
+  fn should_hold_the_buffer_when_frozen() {
+    let mut grains = Grains::new(1000., 8., 0);
+    for i in 0..300 {
+      let freeze = i >= 20;
+      grains.process(
+        (1., 1.),
+        1.,
+        1.,
+        &GrainSettings {
+          freeze,
+          ..settings()
+        },
+      );
+    }
+    assert!(grains.freeze.is_frozen());
+    assert_eq!(grains.grain_delay_line.read(1., Interpolation::Step).0, 0.);
+    assert_eq!(grains.grain_delay_line.read(15., Interpolation::Step).0, 1.);
+    grains.process((1., 1.), 1., 1., &settings());
+    assert_eq!(grains.freeze_offset, 1.);
+  }
+
+  #[test]
+  fn should_only_count_the_freeze_offset_for_frozen_grains() {
+    let mut grains = Grains::new(1000., 8., 0);
+    for _ in 0..100 {
+      grains.process((1., 1.), 1., 1., &settings());
+    }
+    assert_eq!(grains.freeze_offset, 0.);
+  }
+
+  #[test]
+  fn should_update_gain_correction() {
+    let mut grains = Grains::new(10., 8., 0);
+    assert_eq!(grains.gain_correction.get_target(), 0.5);
+    grains.process(
+      (0., 0.),
+      1.,
+      1.,
+      &GrainSettings {
+        voices: 8,
+        ..settings()
+      },
+    );
+    assert_eq!(grains.gain_correction.get_target(), 0.25);
+    grains.process(
+      (0., 0.),
+      1.,
+      1.,
+      &GrainSettings {
+        voices: 8,
+        spray: 100.,
+        ..settings()
+      },
+    );
+    assert_eq!(grains.gain_correction.get_target(), 0.35355338);
+  }
+
+  #[test]
+  fn should_change_voices_without_discontinuities() {
+    let mut grains = Grains::new(1000., 8., 0);
+    let output: Vec<f32> = (0..5000)
+      .map(|i| {
+        let settings = GrainSettings {
+          voices: if i < 3000 { 4 } else { 5 },
+          ..settings()
+        };
+        grains.process((1., 1.), 1., 1., &settings).0
+      })
+      .collect();
+    assert!(output.windows(2).all(|x| (x[1] - x[0]).abs() < 0.01));
+  }
+
+  #[test]
+  fn should_let_removed_voices_finish_their_window() {
+    for (voices, new_voices) in [(8, 2), (32, 1)] {
+      let mut grains = Grains::new(1000., 8., 0);
+      let output: Vec<f32> = (0..5000)
+        .map(|i| {
+          let settings = GrainSettings {
+            voices: if i < 3000 { voices } else { new_voices },
+            ..settings()
+          };
+          grains.process((1., 1.), 1., 1., &settings).0
+        })
+        .collect();
+      assert!(output.windows(2).all(|x| (x[1] - x[0]).abs() < 0.01));
+      assert_eq!(grains.playing_voices, new_voices);
+    }
+  }
+
+  #[test]
+  fn should_keep_the_level_across_voice_counts() {
+    for spray in [2., 100.] {
+      let levels: Vec<f32> = [4, 8, 32]
+        .iter()
+        .map(|voices| {
+          let mut grains = Grains::new(10000., 8., 0);
+          let settings = GrainSettings {
+            voices: *voices,
+            spray,
+            ..settings()
+          };
+          let output: Vec<f32> = (0..40000)
+            .map(|i| {
+              let input = (i as f32 * 0.05).sin();
+              grains.process((input, input), 10., 1., &settings).0
+            })
+            .collect();
+          (output[20000..].iter().map(|x| x * x).sum::<f32>() / 20000.).sqrt()
+        })
+        .collect();
+      assert!(levels
+        .iter()
+        .all(|level| (level / levels[0]).log10().abs() * 20. < 1.5));
+    }
+  }
 }
@@ -1,74 +1,170 @@
 mod delta;
 mod pan;
 mod ramp;
-use super::VOICES;
-use crate::shared::{
-  delay_line::{DelayLine, Interpolation},
-  float_ext::FloatExt,
-};
-use std::f32::consts::PI;
+mod spray;
+mod window;
+use super::GrainSettings;
+use crate::shared::stereo_delay_line::StereoDelayLine;
+use fastrand::Rng;
 use {delta::Delta, pan::Pan, ramp::Ramp};
+pub use {
+  pan::{PanMode, Panning},
+  spray::{Spray, SprayDistribution},
+  window::WindowShape,
+};
+
+/// The state that the grains share, which a grain draws from when it starts.
+pub struct GrainContext<'a> {
+  pub delay_line: &'a StereoDelayLine,
+  pub rng: &'a mut Rng,
+  pub spray: &'a mut Spray,
+  pub panning: &'a mut Panning,
+  pub is_frozen: bool,
+  pub freeze_offset: f32,
+}
 
 #[derive(Clone)]
 pub struct Grain {
   freq: f32,
   start_position: f32,
   pan: f32,
+  voice_position: f32,
   window_size: f32,
   time_ramp: Ramp,
   phase_offset: f32,
+  next_phase_offset: Option<f32>,
   delta: Delta,
   drift: f32,
   is_reversed: bool,
+  is_frozen: bool,
+  is_active: bool,
+  is_retiring: bool,
 }
 
 impl Grain {
-  pub fn new(sample_rate: f32, index: usize) -> Self {
+  pub fn new(sample_rate: f32) -> Self {
     Self {
       freq: 0.,
       start_position: 0.,
       pan: 0.,
+      voice_position: 0.,
       window_size: 0.,
       time_ramp: Ramp::new(sample_rate),
-      phase_offset: (VOICES as f32).recip() * index as f32,
+      phase_offset: 0.,
+      next_phase_offset: None,
       delta: Delta::new(),
       drift: 1.,
       is_reversed: false,
+      is_frozen: false,
+      is_active: false,
+      is_retiring: false,
     }
   }
 
+  /// Moves the grain to a new phase offset once its current window has finished.
+  pub fn set_phase_offset(&mut self, phase_offset: f32) {
+    self.next_phase_offset = Some(phase_offset);
+    self.is_retiring = false;
+  }
+
+  /// Keeps the grain silent until its phase wraps, so it never starts halfway through a window.
+  pub fn activate(&mut self, phase_offset: f32) {
+    self.phase_offset = phase_offset;
+    self.next_phase_offset = None;
+    // A phase can't drop below zero, so the next trigger is the next wrap of the phase
+    self.delta = Delta::new();
+    self.is_active = false;
+    self.is_retiring = false;
+  }
+
+  /// Lets the grain finish its current window and keeps it silent after that.
+  pub fn retire(&mut self) {
+    self.next_phase_offset = None;
+    self.is_retiring = true;
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.is_active
+  }
+
+  /// Whether the grain is playing a window that started while the buffer was frozen.
+  pub fn is_frozen(&self) -> bool {
+    self.is_active && self.is_frozen
+  }
+
+  /// Sets the position of this voice between -1 and 1, which the fixed voices pan mode uses.
+  pub fn set_voice_position(&mut self, voice_position: f32) {
+    self.voice_position = voice_position;
+  }
+
   pub fn process(
     &mut self,
-    grain_delay_line: &DelayLine,
+    context: &mut GrainContext,
     phasor: f32,
     freq: f32,
     speed: f32,
-    spray: f32,
-    drift: f32,
-    reverse: f32,
-    pan: f32,
+    settings: &GrainSettings,
   ) -> (f32, f32) {
     let phase = Self::wrap(phasor + self.phase_offset);
     let trigger = self.delta.process(phase) < 0.;
-    if trigger {
-      self.set_grain_params(freq, spray, drift, reverse, pan);
+    if trigger && self.is_retiring {
+      self.is_active = false;
+      self.is_retiring = false;
+    } else if trigger {
+      match self.next_phase_offset.take() {
+        Some(phase_offset) if phase_offset != self.phase_offset => self.activate(phase_offset),
+        _ => self.start(context, freq, speed, settings),
+      }
+    }
+    if !self.is_active {
+      return (0., 0.);
     }
 
     let (ramp, time) = self.get_ramp_and_time(speed);
-    let window = (ramp * PI).fast_sin() * (phase * PI).fast_sin();
-    let grains_out =
-      grain_delay_line.read(time + self.start_position, Interpolation::Linear) * window;
-    grains_out.pan(self.pan)
+    let time = if self.is_frozen {
+      // The write position doesn't move while frozen, so the time that's left of this grain is added to the delay time.
+      time + self.start_position + (1. - phase) * self.window_size + context.freeze_offset
+    } else {
+      time + self.start_position
+    };
+    let window = settings.window_shape.process(ramp, phase, settings.taper);
+    let read_speed = speed * self.drift;
+    let grains_out = context
+      .delay_line
+      .read_at_speed(time, read_speed, settings.interpolation);
+    (grains_out.0 * window, grains_out.1 * window).pan(self.pan)
   }
 
-  fn set_grain_params(&mut self, freq: f32, spray: f32, drift: f32, reverse: f32, pan: f32) {
+  fn start(&mut self, context: &mut GrainContext, freq: f32, speed: f32, settings: &GrainSettings) {
+    let start_position = context.spray.next(context.rng);
+    self.set_grain_params(
+      context.rng,
+      freq,
+      start_position,
+      settings.drift,
+      settings.reverse,
+    );
+    self.pan = context
+      .panning
+      .next(context.rng, self.voice_position, speed * self.drift);
+    self.is_frozen = context.is_frozen;
+    self.is_active = true;
+  }
+
+  fn set_grain_params(
+    &mut self,
+    rng: &mut Rng,
+    freq: f32,
+    start_position: f32,
+    drift: f32,
+    reverse: f32,
+  ) {
     self.freq = freq;
     self.time_ramp.start();
-    self.start_position = fastrand::f32() * spray;
-    self.pan = (fastrand::f32() * pan * 2. - pan) * 50.;
-    self.is_reversed = fastrand::f32() <= reverse;
+    self.start_position = start_position;
+    self.is_reversed = rng.f32() <= reverse;
     self.window_size = freq.recip() * 1000.;
-    self.set_drift(drift);
+    self.set_drift(rng, drift);
   }
 
   fn get_speed_for_delay_line(&self, speed: f32) -> f32 {
@@ -79,8 +175,8 @@ impl Grain {
     }
   }
 
-  fn set_drift(&mut self, drift: f32) {
-    let random_pitch = fastrand::f32() * drift * 2. - drift;
+  fn set_drift(&mut self, rng: &mut Rng, drift: f32) {
+    let random_pitch = rng.f32() * drift * 2. - drift;
     self.drift = 2_f32.powf(random_pitch / 12.);
   }
 
@@ -1,20 +1,105 @@
 use crate::shared::float_ext::FloatExt;
-use std::f32::consts::PI;
+use fastrand::Rng;
+use std::f32::consts::{PI, TAU};
+
+const ROTATION_STEP: f32 = 0.125;
 
 pub trait Pan {
   fn pan(self, pan: f32) -> (f32, f32);
 }
 
 impl Pan for f32 {
+  fn pan(self, pan: f32) -> (f32, f32) {
+    (self, self).pan(pan)
+  }
+}
+
+impl Pan for (f32, f32) {
   fn pan(self, pan: f32) -> (f32, f32) {
     let radians = (pan + 50.) * 0.005 * PI;
-    (self * radians.fast_cos(), self * radians.fast_sin())
+    (self.0 * radians.fast_cos(), self.1 * radians.fast_sin())
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PanMode {
+  Random,
+  Alternating,
+  Rotating,
+  Pitch,
+  FixedVoices,
+}
+
+impl From<i32> for PanMode {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::Alternating,
+      2 => Self::Rotating,
+      3 => Self::Pitch,
+      4 => Self::FixedVoices,
+      _ => Self::Random,
+    }
+  }
+}
+
+/// Picks the pan positions of the grains. The state is shared by all grains, so the alternating
+/// and rotating modes move along for every grain that starts.
+pub struct Panning {
+  spread: f32,
+  mode: PanMode,
+  is_right: bool,
+  rotation: f32,
+}
+
+impl Panning {
+  pub fn new() -> Self {
+    Self {
+      spread: 0.,
+      mode: PanMode::Random,
+      is_right: false,
+      rotation: 0.,
+    }
+  }
+
+  pub fn set(&mut self, spread: f32, mode: PanMode) {
+    self.spread = spread;
+    self.mode = mode;
+  }
+
+  /// Returns a pan position between -50 and 50. The voice position ranges from -1 to 1 and the
+  /// speed is the playback speed of the grain.
+  pub fn next(&mut self, rng: &mut Rng, voice_position: f32, speed: f32) -> f32 {
+    let position = match self.mode {
+      PanMode::Random => rng.f32() * 2. - 1.,
+      PanMode::Alternating => self.alternate(),
+      PanMode::Rotating => {
+        self.rotation = (self.rotation + ROTATION_STEP).fract();
+        (self.rotation * TAU).fast_sin()
+      }
+      PanMode::Pitch => {
+        // Two octaves down stays in the center and two octaves up is panned fully
+        let width = ((speed.log2() + 2.) * 0.25).clamp(0., 1.);
+        self.alternate() * width
+      }
+      PanMode::FixedVoices => voice_position,
+    };
+    position * self.spread * 50.
+  }
+
+  fn alternate(&mut self) -> f32 {
+    self.is_right = !self.is_right;
+    if self.is_right {
+      1.
+    } else {
+      -1.
+    }
   }
 }
 
 #[cfg(test)]
 mod tests {
-  use super::Pan;
+  use super::{Pan, PanMode, Panning};
+  use fastrand::Rng;
   use std::f32::consts::FRAC_1_SQRT_2;
 
   fn assert_approximately_eq(left: (f32, f32), right: (f32, f32)) {
@@ -34,4 +119,33 @@ mod tests {
     assert_approximately_eq(1f32.pan(50.), (0., 1.));
     assert_approximately_eq(1f32.pan(0.), (FRAC_1_SQRT_2, FRAC_1_SQRT_2));
   }
+
+  #[test]
+  fn stereo_pan() {
+    assert_approximately_eq((1f32, 0.5).pan(-50.), (1., 0.));
+    assert_approximately_eq((1f32, 0.5).pan(50.), (0., 0.5));
+    assert_approximately_eq((1f32, 0.5).pan(0.), (FRAC_1_SQRT_2, FRAC_1_SQRT_2 * 0.5));
+  }
+
+  #[test]
+  fn should_pick_positions_by_mode() {
+    let mut rng = Rng::with_seed(42);
+    let mut panning = Panning::new();
+    panning.set(1., PanMode::Alternating);
+    let positions: Vec<f32> = (0..4).map(|_| panning.next(&mut rng, 0., 1.)).collect();
+    assert_eq!(positions, vec![50., -50., 50., -50.]);
+
+    panning.set(0.5, PanMode::FixedVoices);
+    assert_eq!(panning.next(&mut rng, -1., 1.), -25.);
+
+    panning.set(1., PanMode::Pitch);
+    assert_eq!(panning.next(&mut rng, 0., 0.25), 0.);
+    assert_eq!(panning.next(&mut rng, 0., 4.).abs(), 50.);
+    assert_eq!(panning.next(&mut rng, 0., 1.).abs(), 25.);
+
+    panning.set(1., PanMode::Rotating);
+    let positions: Vec<f32> = (0..8).map(|_| panning.next(&mut rng, 0., 1.)).collect();
+    assert!(positions.iter().all(|position| position.abs() <= 50.));
+    assert!((positions[1] - 50.).abs() < 0.1 && (positions[5] + 50.).abs() < 0.1);
+  }
 }
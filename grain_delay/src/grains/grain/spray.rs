@@ -0,0 +1,117 @@
+use fastrand::Rng;
+use std::f32::consts::TAU;
+
+const QUANTIZE_STEPS: u32 = 4;
+const WALK_STEP: f32 = 0.125;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SprayDistribution {
+  Uniform,
+  Gaussian,
+  Exponential,
+  Quantized,
+  Walk,
+}
+
+impl From<i32> for SprayDistribution {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::Gaussian,
+      2 => Self::Exponential,
+      3 => Self::Quantized,
+      4 => Self::Walk,
+      _ => Self::Uniform,
+    }
+  }
+}
+
+/// Picks the start positions of the grains within the spray time. The state is shared by all
+/// grains, so the walk moves a step for every grain that starts.
+pub struct Spray {
+  amount: f32,
+  distribution: SprayDistribution,
+  walk: f32,
+}
+
+impl Spray {
+  pub fn new() -> Self {
+    Self {
+      amount: 0.,
+      distribution: SprayDistribution::Uniform,
+      walk: 0.,
+    }
+  }
+
+  pub fn set(&mut self, amount: f32, distribution: SprayDistribution) {
+    self.amount = amount;
+    self.distribution = distribution;
+  }
+
+  /// Returns the start position in milliseconds, where zero is the most recent audio.
+  pub fn next(&mut self, rng: &mut Rng) -> f32 {
+    let position = match self.distribution {
+      SprayDistribution::Uniform => rng.f32(),
+      SprayDistribution::Gaussian => {
+        // Box-Muller transform, centered in the spray time with a standard deviation of a sixth
+        let radius = (-2. * (1. - rng.f32()).ln()).sqrt();
+        (0.5 + radius * (rng.f32() * TAU).cos() / 6.).clamp(0., 1.)
+      }
+      SprayDistribution::Exponential => (-(1. - rng.f32()).ln() * 0.25).min(1.),
+      SprayDistribution::Quantized => rng.u32(0..=QUANTIZE_STEPS) as f32 / QUANTIZE_STEPS as f32,
+      SprayDistribution::Walk => {
+        // Bounces back and forth through the spray time
+        self.walk = (self.walk + WALK_STEP) % 2.;
+        1. - (1. - self.walk).abs()
+      }
+    };
+    position * self.amount
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Spray, SprayDistribution};
+  use fastrand::Rng;
+
+  fn positions(distribution: SprayDistribution) -> Vec<f32> {
+    let mut rng = Rng::with_seed(42);
+    let mut spray = Spray::new();
+    spray.set(100., distribution);
+    (0..1000).map(|_| spray.next(&mut rng)).collect()
+  }
+
+  fn mean(positions: &[f32]) -> f32 {
+    positions.iter().sum::<f32>() / positions.len() as f32
+  }
+
+  #[test]
+  fn should_stay_within_the_spray_time() {
+    for distribution in [
+      SprayDistribution::Uniform,
+      SprayDistribution::Gaussian,
+      SprayDistribution::Exponential,
+      SprayDistribution::Quantized,
+      SprayDistribution::Walk,
+    ] {
+      assert!(positions(distribution)
+        .iter()
+        .all(|position| (0. ..=100.).contains(position)));
+    }
+  }
+
+  #[test]
+  fn should_shape_the_distribution() {
+    let gaussian = positions(SprayDistribution::Gaussian);
+    assert!((mean(&gaussian) - 50.).abs() < 3.);
+    assert!(gaussian.iter().filter(|x| (**x - 50.).abs() < 17.).count() > 600);
+
+    assert!(mean(&positions(SprayDistribution::Exponential)) < 30.);
+    assert!(positions(SprayDistribution::Quantized)
+      .iter()
+      .all(|position| position % 25. == 0.));
+    assert_eq!(
+      positions(SprayDistribution::Walk)[..10],
+      [12.5, 25., 37.5, 50., 62.5, 75., 87.5, 100., 87.5, 75.]
+    );
+  }
+}
@@ -0,0 +1,119 @@
+use crate::shared::float_ext::FloatExt;
+use std::f32::consts::PI;
+
+const EXPODEC_CURVE: f32 = 6.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowShape {
+  Hann,
+  Tukey,
+  Gaussian,
+  Trapezoid,
+  Expodec,
+  Rexpodec,
+}
+
+impl From<i32> for WindowShape {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::Tukey,
+      2 => Self::Gaussian,
+      3 => Self::Trapezoid,
+      4 => Self::Expodec,
+      5 => Self::Rexpodec,
+      _ => Self::Hann,
+    }
+  }
+}
+
+impl WindowShape {
+  /// Returns the window gain. The ramp is the playback position of the grain, the phase is the position in time.
+  /// The taper (0 to 1) sets the slope of the Tukey and trapezoid windows and the width of the Gaussian window.
+  pub fn process(&self, ramp: f32, phase: f32, taper: f32) -> f32 {
+    match self {
+      Self::Hann => (ramp * PI).fast_sin() * (phase * PI).fast_sin(),
+      Self::Tukey => Self::tukey(phase, taper),
+      Self::Gaussian => Self::gaussian(phase, taper),
+      Self::Trapezoid => Self::trapezoid(phase, taper),
+      Self::Expodec => Self::expodec(phase),
+      Self::Rexpodec => Self::expodec(1. - phase),
+    }
+  }
+
+  fn tukey(x: f32, taper: f32) -> f32 {
+    let edge = taper * 0.5;
+    if x < edge {
+      0.5 - (x / edge * PI).fast_cos() * 0.5
+    } else if x > 1. - edge {
+      0.5 - ((1. - x) / edge * PI).fast_cos() * 0.5
+    } else {
+      1.
+    }
+  }
+
+  fn gaussian(x: f32, taper: f32) -> f32 {
+    let sigma = 0.05 + taper * 0.25;
+    let edge = (-0.125 / (sigma * sigma)).exp();
+    let deviation = (x - 0.5) / sigma;
+    let gaussian = (-0.5 * deviation * deviation).exp();
+    ((gaussian - edge) / (1. - edge)).max(0.)
+  }
+
+  fn trapezoid(x: f32, taper: f32) -> f32 {
+    let edge = taper * 0.5;
+    if edge > 0. {
+      (x.min(1. - x) / edge).min(1.)
+    } else {
+      1.
+    }
+  }
+
+  fn expodec(x: f32) -> f32 {
+    let end = (-EXPODEC_CURVE).exp();
+    (((-EXPODEC_CURVE * x).exp() - end) / (1. - end)).max(0.)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::WindowShape;
+
+  fn assert_approximately_eq(left: f32, right: f32) {
+    assert_eq!((left * 100.).round() / 100., (right * 100.).round() / 100.)
+  }
+
+  #[test]
+  fn tukey() {
+    let shape = WindowShape::Tukey;
+    assert_approximately_eq(shape.process(0., 0., 0.5), 0.);
+    assert_approximately_eq(shape.process(0.125, 0.125, 0.5), 0.5);
+    assert_approximately_eq(shape.process(0.5, 0.5, 0.5), 1.);
+    assert_approximately_eq(shape.process(1., 1., 0.5), 0.);
+    assert_approximately_eq(shape.process(0.1, 0.1, 0.), 1.);
+  }
+
+  #[test]
+  fn gaussian() {
+    let shape = WindowShape::Gaussian;
+    assert_approximately_eq(shape.process(0., 0., 1.), 0.);
+    assert_approximately_eq(shape.process(0.5, 0.5, 1.), 1.);
+    assert_approximately_eq(shape.process(1., 1., 1.), 0.);
+  }
+
+  #[test]
+  fn trapezoid() {
+    let shape = WindowShape::Trapezoid;
+    assert_approximately_eq(shape.process(0., 0., 0.5), 0.);
+    assert_approximately_eq(shape.process(0.125, 0.125, 0.5), 0.5);
+    assert_approximately_eq(shape.process(0.5, 0.5, 0.5), 1.);
+    assert_approximately_eq(shape.process(0.875, 0.875, 0.5), 0.5);
+  }
+
+  #[test]
+  fn expodec() {
+    assert_approximately_eq(WindowShape::Expodec.process(0., 0., 0.), 1.);
+    assert_approximately_eq(WindowShape::Expodec.process(1., 1., 0.), 0.);
+    assert_approximately_eq(WindowShape::Rexpodec.process(0., 0., 0.), 0.);
+    assert_approximately_eq(WindowShape::Rexpodec.process(1., 1., 0.), 1.);
+  }
+}
@@ -0,0 +1,149 @@
+use crate::{modulation::CONTROL_INTERVAL, shared::float_ext::FloatExt};
+use fastrand::Rng;
+use std::f32::consts::{PI, TAU};
+
+pub const LFO_COUNT: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LfoShape {
+  Sine,
+  Triangle,
+  Square,
+  SampleAndHold,
+  SmoothRandom,
+}
+
+impl From<i32> for LfoShape {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::Triangle,
+      2 => Self::Square,
+      3 => Self::SampleAndHold,
+      4 => Self::SmoothRandom,
+      _ => Self::Sine,
+    }
+  }
+}
+
+/// A low frequency oscillator that advances once per control period.
+pub struct Lfo {
+  control_period: f32,
+  phase: f32,
+  seed: u64,
+  rng: Rng,
+  previous_random: f32,
+  next_random: f32,
+}
+
+impl Lfo {
+  pub fn new(sample_rate: f32, seed: u64) -> Self {
+    let mut lfo = Self {
+      control_period: CONTROL_INTERVAL as f32 / sample_rate,
+      phase: 0.,
+      seed,
+      rng: Rng::with_seed(seed),
+      previous_random: 0.,
+      next_random: 0.,
+    };
+    lfo.reseed(seed);
+    lfo
+  }
+
+  pub fn reseed(&mut self, seed: u64) {
+    self.seed = seed;
+    self.rng.seed(seed);
+    // The random shapes start from random values instead of holding zero for the first cycle
+    self.previous_random = self.next_random();
+    self.next_random = self.next_random();
+  }
+
+  /// Starts over from the phase and the random values of a new LFO with the same seed.
+  pub fn reset(&mut self, sample_rate: f32) {
+    self.control_period = CONTROL_INTERVAL as f32 / sample_rate;
+    self.phase = 0.;
+    self.reseed(self.seed);
+  }
+
+  /// Returns a value between -1 and 1. The random shapes pick a new value every cycle.
+  pub fn process(&mut self, freq: f32, shape: LfoShape) -> f32 {
+    self.phase += freq * self.control_period;
+    if self.phase >= 1. {
+      self.phase = self.phase.fract();
+      self.previous_random = self.next_random;
+      self.next_random = self.next_random();
+    }
+
+    match shape {
+      LfoShape::Sine => (self.phase * TAU).fast_sin(),
+      LfoShape::Triangle => 1. - 4. * ((self.phase + 0.25).fract() - 0.5).abs(),
+      LfoShape::Square => {
+        if self.phase < 0.5 {
+          1.
+        } else {
+          -1.
+        }
+      }
+      LfoShape::SampleAndHold => self.next_random,
+      LfoShape::SmoothRandom => {
+        let factor = (1. - (self.phase * PI).fast_cos()) * 0.5;
+        self.previous_random.mix(self.next_random, factor)
+      }
+    }
+  }
+
+  fn next_random(&mut self) -> f32 {
+    self.rng.f32() * 2. - 1.
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Lfo, LfoShape};
+  use crate::modulation::CONTROL_INTERVAL;
+
+  fn render(shape: LfoShape) -> Vec<f32> {
+    // Four control periods per cycle
+    let mut lfo = Lfo::new(CONTROL_INTERVAL as f32 * 4., 42);
+    (0..8).map(|_| lfo.process(1., shape)).collect()
+  }
+
+  #[test]
+  fn should_generate_periodic_shapes() {
+    let triangle = render(LfoShape::Triangle);
+    assert_eq!(triangle, vec![1., 0., -1., 0., 1., 0., -1., 0.]);
+    let square = render(LfoShape::Square);
+    assert_eq!(square, vec![1., -1., -1., 1., 1., -1., -1., 1.]);
+    let sine = render(LfoShape::Sine);
+    assert!((sine[0] - 1.).abs() < 1e-3 && (sine[2] + 1.).abs() < 1e-3);
+  }
+
+  #[test]
+  fn should_hold_random_values_for_a_cycle() {
+    let sample_and_hold = render(LfoShape::SampleAndHold);
+    assert!(sample_and_hold[..3]
+      .iter()
+      .all(|value| *value == sample_and_hold[0] && *value != 0.));
+    assert!(sample_and_hold[3..7]
+      .iter()
+      .all(|value| *value == sample_and_hold[3] && value.abs() <= 1.));
+    assert!(sample_and_hold[7] != sample_and_hold[3]);
+
+    let smooth_random = render(LfoShape::SmoothRandom);
+    assert!(smooth_random.iter().all(|value| value.abs() <= 1.));
+    assert!(smooth_random[0] != 0.);
+  }
+
+  #[test]
+  fn should_start_over_after_a_reset() {
+    let sample_rate = CONTROL_INTERVAL as f32 * 4.;
+    let mut lfo = Lfo::new(sample_rate, 42);
+    let first: Vec<f32> = (0..8)
+      .map(|_| lfo.process(1., LfoShape::SmoothRandom))
+      .collect();
+    lfo.reset(sample_rate);
+    let second: Vec<f32> = (0..8)
+      .map(|_| lfo.process(1., LfoShape::SmoothRandom))
+      .collect();
+    assert_eq!(first, second);
+  }
+}
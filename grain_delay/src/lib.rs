@@ -1,68 +1,678 @@
 include!(concat!(env!("OUT_DIR"), "/constants.rs"));
+mod config;
 mod dc_block;
+mod diffuser;
+mod ducker;
+mod feedback;
 mod shared {
   pub mod delay_line;
+  pub mod envelope_follower;
   pub mod float_ext;
+  pub mod freeze;
+  pub mod stereo_delay_line;
 }
 mod grains;
+mod lfo;
 mod mix;
-mod one_pole_filter_stereo;
+mod modulation;
+mod oversampling;
+mod param_event;
 mod params;
+mod saturation;
+mod state_variable_filter;
+mod topology;
 mod variable_delay_line;
-pub use params::Params;
+use std::iter;
+pub use {
+  config::GrainDelayConfig,
+  feedback::FeedbackMode,
+  grains::{PanMode, SprayDistribution, WindowShape, MAX_VOICES, MIN_VOICES},
+  lfo::{LfoShape, LFO_COUNT},
+  modulation::ModDestination,
+  oversampling::Oversampling,
+  param_event::ParamEvent,
+  params::{
+    BeatDivision, LfoParams, ParamDescriptor, ParamError, ParamId, ParamKind, ParamValues, Params,
+    Unit, PARAMS, PARAM_COUNT,
+  },
+  saturation::SaturationCurve,
+  shared::delay_line::InterpolationQuality,
+  state_variable_filter::FilterMode,
+  topology::Topology,
+  variable_delay_line::TimeMode,
+};
 use {
-  dc_block::DcBlock, grains::Grains, mix::Mix, one_pole_filter_stereo::OnePoleFilterStereo,
-  params::Smoother, shared::delay_line::Interpolation, variable_delay_line::VariableDelayLine,
+  diffuser::Diffuser,
+  ducker::Ducker,
+  feedback::Feedback,
+  grains::{GrainSettings, Grains},
+  lfo::Lfo,
+  mix::Mix,
+  modulation::{Modulation, CONTROL_INTERVAL},
+  oversampling::{LatencyCompensation, Oversampler},
+  param_event::segments,
+  params::Smoother,
+  saturation::Saturation,
+  shared::envelope_follower::EnvelopeFollower,
+  state_variable_filter::StateVariableFilter,
+  variable_delay_line::VariableDelayLine,
 };
 
+/// The longest delay time in seconds that a `GrainDelayConfig` can allocate for.
+pub const MAX_DELAY_TIME: f32 = 30.;
+
 pub struct GrainDelay {
   variable_delay_line: VariableDelayLine,
-  low_pass_filter: OnePoleFilterStereo,
+  filter: StateVariableFilter,
+  diffuser: Diffuser,
   grains: Grains,
-  dc_block: DcBlock,
+  oversampler: Oversampler,
+  latency_compensation: LatencyCompensation,
+  feedback: Feedback,
+  ducker: Ducker,
   mix: Mix,
+  lfos: [Lfo; LFO_COUNT],
+  envelope_follower: EnvelopeFollower,
+  envelope: f32,
+  modulation: Modulation,
+  control_counter: usize,
+  config: GrainDelayConfig,
+  sample_rate: f32,
+  max_sample_rate: f32,
+  seed: u64,
 }
 
 impl GrainDelay {
   pub fn new(sample_rate: f32) -> Self {
-    Self {
-      variable_delay_line: VariableDelayLine::new((sample_rate * 5.) as usize, sample_rate),
-      low_pass_filter: OnePoleFilterStereo::new(sample_rate),
-      grains: Grains::new(sample_rate),
-      dc_block: DcBlock::new(sample_rate),
+    Self::with_config(sample_rate, GrainDelayConfig::default())
+  }
+
+  /// Creates an instance with its own seeded random number generator, so renders are reproducible.
+  pub fn with_seed(sample_rate: f32, seed: u64) -> Self {
+    Self::with_config_and_seed(sample_rate, GrainDelayConfig::default(), seed)
+  }
+
+  /// Creates an instance with buffers sized for the limits of the config.
+  /// Oversampling adds latency, which is reported by `latency`.
+  pub fn with_config(sample_rate: f32, config: GrainDelayConfig) -> Self {
+    Self::with_config_and_seed(sample_rate, config, fastrand::u64(..))
+  }
+
+  /// The buffers are allocated for the highest of the sample rate and the max sample rate of the
+  /// config.
+  pub fn with_config_and_seed(sample_rate: f32, config: GrainDelayConfig, seed: u64) -> Self {
+    let config = config.clamped();
+    let max_sample_rate = sample_rate.max(config.max_sample_rate);
+    let oversampler = Oversampler::new(config.oversampling);
+    let latency = oversampler.latency();
+    let mut grain_delay = Self {
+      variable_delay_line: VariableDelayLine::new(
+        (max_sample_rate * config.max_time() * 0.001) as usize,
+        max_sample_rate,
+      ),
+      filter: StateVariableFilter::new(sample_rate),
+      diffuser: Diffuser::new(max_sample_rate),
+      grains: Grains::new(
+        max_sample_rate * config.oversampling.factor() as f32,
+        config.max_grain_delay_time(),
+        seed,
+      ),
+      oversampler,
+      latency_compensation: LatencyCompensation::new(latency),
+      feedback: Feedback::new(sample_rate),
+      ducker: Ducker::new(sample_rate),
       mix: Mix::new(),
+      lfos: [1, 2].map(|offset| Lfo::new(sample_rate, seed.wrapping_add(offset))),
+      envelope_follower: EnvelopeFollower::new(sample_rate),
+      envelope: 0.,
+      modulation: Modulation::new(),
+      control_counter: 0,
+      config,
+      sample_rate,
+      max_sample_rate,
+      seed,
+    };
+    grain_delay.reset();
+    grain_delay
+  }
+
+  /// Clears the buffers, filters and ramps, so processing continues from silence.
+  pub fn reset(&mut self) {
+    let sample_rate = self.sample_rate;
+    self.variable_delay_line.reset(sample_rate);
+    self.filter = StateVariableFilter::new(sample_rate);
+    self.diffuser.reset(sample_rate);
+    self
+      .grains
+      .reset(sample_rate * self.config.oversampling.factor() as f32);
+    self.oversampler.reset();
+    self.latency_compensation.reset();
+    self.feedback = Feedback::new(sample_rate);
+    self.ducker = Ducker::new(sample_rate);
+    self.mix = Mix::new();
+    self.lfos.iter_mut().for_each(|lfo| lfo.reset(sample_rate));
+    self.envelope_follower = EnvelopeFollower::new(sample_rate);
+    self.envelope = 0.;
+    self.modulation = Modulation::new();
+    self.control_counter = 0;
+  }
+
+  /// Changes the sample rate and resets. The buffers are reused up to the max sample rate of the
+  /// config, while a higher sample rate reallocates them and starts over from the last seed.
+  pub fn set_sample_rate(&mut self, sample_rate: f32) {
+    if sample_rate > self.max_sample_rate {
+      *self = Self::with_config_and_seed(sample_rate, self.config, self.seed);
+    } else {
+      self.sample_rate = sample_rate;
+      self.reset();
     }
   }
 
+  /// Returns the latency in samples.
+  pub fn latency(&self) -> usize {
+    self.oversampler.latency()
+  }
+
+  pub fn reseed(&mut self, seed: u64) {
+    self.seed = seed;
+    self.grains.reseed(seed);
+    self
+      .lfos
+      .iter_mut()
+      .zip(1..)
+      .for_each(|(lfo, offset)| lfo.reseed(seed.wrapping_add(offset)));
+  }
+
   pub fn process(&mut self, input: f32, params: &mut Params) -> (f32, f32) {
+    self.process_stereo((input, input), params)
+  }
+
+  pub fn process_stereo(&mut self, input: (f32, f32), params: &mut Params) -> (f32, f32) {
+    let mut output = (0., 0.);
+    self.process_frames(iter::once((input, (&mut output.0, &mut output.1))), params);
+    output
+  }
+
+  /// Processes a block of mono input into the left and right output buffers.
+  /// The output buffers should be as long as the input buffer.
+  pub fn process_block(
+    &mut self,
+    input: &[f32],
+    left: &mut [f32],
+    right: &mut [f32],
+    params: &mut Params,
+  ) {
+    let inputs = input.iter().map(|input| (*input, *input));
+    self.process_frames(inputs.zip(left.iter_mut().zip(right.iter_mut())), params);
+  }
+
+  /// Processes a block of stereo input into the left and right output buffers.
+  /// The output buffers should be as long as the input buffers.
+  pub fn process_block_stereo(
+    &mut self,
+    input_left: &[f32],
+    input_right: &[f32],
+    left: &mut [f32],
+    right: &mut [f32],
+    params: &mut Params,
+  ) {
+    let inputs = input_left
+      .iter()
+      .zip(input_right.iter())
+      .map(|(left, right)| (*left, *right));
+    self.process_frames(inputs.zip(left.iter_mut().zip(right.iter_mut())), params);
+  }
+
+  /// Processes a block of mono input while applying each event at its sample offset.
+  /// The events should be sorted by offset and apply maps an event onto the params.
+  pub fn process_block_with_events(
+    &mut self,
+    input: &[f32],
+    left: &mut [f32],
+    right: &mut [f32],
+    params: &mut Params,
+    events: &[ParamEvent],
+    mut apply: impl FnMut(&ParamEvent, &mut Params),
+  ) {
+    for (range, event) in segments(input.len(), events) {
+      self.process_block(
+        &input[range.clone()],
+        &mut left[range.clone()],
+        &mut right[range],
+        params,
+      );
+      if let Some(event) = event {
+        apply(event, params);
+      }
+    }
+  }
+
+  /// Processes a block of stereo input while applying each event at its sample offset.
+  /// The events should be sorted by offset and apply maps an event onto the params.
+  pub fn process_block_stereo_with_events(
+    &mut self,
+    input: (&[f32], &[f32]),
+    left: &mut [f32],
+    right: &mut [f32],
+    params: &mut Params,
+    events: &[ParamEvent],
+    mut apply: impl FnMut(&ParamEvent, &mut Params),
+  ) {
+    for (range, event) in segments(input.0.len(), events) {
+      self.process_block_stereo(
+        &input.0[range.clone()],
+        &input.1[range.clone()],
+        &mut left[range.clone()],
+        &mut right[range],
+        params,
+      );
+      if let Some(event) = event {
+        apply(event, params);
+      }
+    }
+  }
+
+  // The parameters that aren't smoothed stay the same for the whole block.
+  fn process_frames<'a>(
+    &mut self,
+    frames: impl Iterator<Item = ((f32, f32), (&'a mut f32, &'a mut f32))>,
+    params: &mut Params,
+  ) {
     let Params {
-      spray,
-      freq,
       drift,
       reverse,
-      time,
-      spread,
+      voices,
+      window_shape,
+      taper,
+      feedback_mode,
+      filter_mode,
+      saturation_curve,
+      freeze,
+      duck_threshold,
+      duck_attack,
+      duck_release,
+      envelope_attack,
+      envelope_release,
+      topology,
+      spray_distribution,
+      pan_mode,
+      interpolation_quality,
+      time_mode,
+      time_fade,
       ..
     } = *params;
-    let speed = params.speed.next();
-    let feedback = params.feedback.next();
-    let filter = params.filter.next();
-    let mix = params.mix.next();
+    let max_time = self.config.max_time();
+    let max_speed = self.config.max_speed();
+    let max_spray = self.config.max_spray();
+    let min_frequency = self.config.min_frequency;
 
-    let delay_out = self.variable_delay_line.read(time, Interpolation::Step);
-    let grain_delay_out = self
-      .grains
-      .process(delay_out, spray, freq, speed, drift, reverse, spread);
-    let filter_out = self.low_pass_filter.process(grain_delay_out, filter);
-    let feedback_out = self.apply_feedback(filter_out, feedback);
-    self.variable_delay_line.write(input + feedback_out);
+    for (input, (left, right)) in frames {
+      self.envelope = self.envelope_follower.process(
+        input.0.abs().max(input.1.abs()),
+        envelope_attack,
+        envelope_release,
+      );
+      if self.control_counter == 0 {
+        self.update_modulation(params);
+      }
+      self.control_counter = (self.control_counter + 1) % CONTROL_INTERVAL;
+      self.modulation.next();
+
+      let spray = self
+        .modulation
+        .apply(ModDestination::Spray, params.spray)
+        .min(max_spray);
+      let freq = self
+        .modulation
+        .apply(ModDestination::Frequency, params.freq)
+        .max(min_frequency);
+      let time = self
+        .modulation
+        .apply(ModDestination::Time, params.time)
+        .min(max_time);
+      let spread = self.modulation.apply(ModDestination::Spread, params.spread);
+      let speed = self
+        .modulation
+        .apply(ModDestination::Pitch, params.speed.next())
+        .min(max_speed);
+      let feedback = self
+        .modulation
+        .apply(ModDestination::Feedback, params.feedback.next());
+      let cross_feedback = params.cross_feedback.next();
+      let filter = self
+        .modulation
+        .apply(ModDestination::Filter, params.filter.next());
+      let resonance = params.resonance.next();
+      let diffusion = params.diffusion.next();
+      let saturation = Saturation {
+        curve: saturation_curve,
+        drive: params.drive.next(),
+      };
+      let mix = self
+        .modulation
+        .apply(ModDestination::Mix, params.mix.next());
+      let duck_amount = params.duck_amount.next();
+
+      let grain_settings = GrainSettings {
+        spray,
+        spray_distribution,
+        drift,
+        reverse,
+        spread,
+        pan_mode,
+        voices,
+        window_shape,
+        taper,
+        interpolation: interpolation_quality.grains(),
+        freeze,
+      };
+      let mut process_grains = |input| {
+        self.oversampler.process(input, |input| {
+          self.grains.process(input, freq, speed, &grain_settings)
+        })
+      };
+      let mut filter_and_diffuse = |input| {
+        let filter_out = self.filter.process(input, filter, resonance, filter_mode);
+        self.diffuser.process(filter_out, diffusion)
+      };
+      let delay_out =
+        self
+          .variable_delay_line
+          .read(time, time_mode, time_fade, interpolation_quality.delay());
+      let (delay_input, filter_out, wet) = match topology {
+        Topology::InLoop => {
+          let grains_out = process_grains(delay_out);
+          let filter_out = filter_and_diffuse(grains_out);
+          (input, filter_out, filter_out)
+        }
+        Topology::PostDelay => {
+          let filter_out = filter_and_diffuse(delay_out);
+          (input, filter_out, process_grains(filter_out))
+        }
+        Topology::PreDelay => {
+          let filter_out = filter_and_diffuse(delay_out);
+          (process_grains(input), filter_out, filter_out)
+        }
+      };
+      let delay_in = self.feedback.process(
+        delay_input,
+        filter_out,
+        feedback,
+        cross_feedback,
+        feedback_mode,
+        saturation,
+      );
+      // Outside the loop the grains hold the frozen sound, so the delay keeps repeating what they play
+      self
+        .variable_delay_line
+        .write(delay_in, freeze && topology == Topology::InLoop);
+
+      // The dry signal is delayed by the latency of the oversampled grains
+      let dry = self.latency_compensation.process(input);
+      let wet = self.ducker.process(
+        dry,
+        wet,
+        duck_threshold,
+        duck_amount,
+        duck_attack,
+        duck_release,
+      );
+      (*left, *right) = self.mix.process(dry, wet, mix);
+    }
+  }
+
+  fn update_modulation(&mut self, params: &Params) {
+    let targets = self
+      .lfos
+      .iter_mut()
+      .zip(params.lfos.iter())
+      .map(|(lfo, lfo_params)| {
+        (
+          lfo_params.destination,
+          lfo.process(lfo_params.freq, lfo_params.shape) * lfo_params.depth,
+        )
+      })
+      .chain(iter::once((
+        params.envelope_destination,
+        self.envelope * params.envelope_depth,
+      )));
+    self.modulation.set_targets(targets);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    GrainDelay, GrainDelayConfig, Oversampling, ParamEvent, ParamValues, Params, TimeMode, Topology,
+  };
+
+  fn set_params(params: &mut Params) {
+    set_params_with_mix(params, 100.);
+  }
+
+  fn set_params_with_mix(params: &mut Params, mix: f32) {
+    params.set(&ParamValues {
+      spray: 50.,
+      frequency: 7.,
+      pitch: 12.,
+      drift: 50.,
+      reverse: 50.,
+      time: 10.,
+      feedback: 50.,
+      filter: 5000.,
+      spread: 100.,
+      mix,
+      ..Default::default()
+    });
+  }
+
+  fn render(grain_delay: &mut GrainDelay) -> Vec<(f32, f32)> {
+    let mut params = Params::new(44100.);
+    (0..44100)
+      .map(|i| {
+        if i % 128 == 0 {
+          set_params(&mut params);
+        }
+        grain_delay.process((i as f32 * 0.01).sin(), &mut params)
+      })
+      .collect()
+  }
+
+  #[test]
+  fn should_render_deterministically_with_the_same_seed() {
+    let first = render(&mut GrainDelay::with_seed(44100., 42));
+    let second = render(&mut GrainDelay::with_seed(44100., 42));
+    assert!(first == second);
+
+    let mut reseeded = GrainDelay::with_seed(44100., 7);
+    reseeded.reseed(42);
+    assert!(render(&mut reseeded) == first);
+    assert!(render(&mut GrainDelay::with_seed(44100., 7)) != first);
+  }
+
+  #[test]
+  fn should_render_like_a_new_instance_after_a_reset() {
+    let expected = render(&mut GrainDelay::with_seed(44100., 42));
+
+    let mut grain_delay = GrainDelay::with_seed(44100., 42);
+    render(&mut grain_delay);
+    grain_delay.reseed(42);
+    grain_delay.reset();
+    assert!(render(&mut grain_delay) == expected);
+
+    let mut grain_delay = GrainDelay::with_seed(48000., 42);
+    grain_delay.set_sample_rate(44100.);
+    assert!(render(&mut grain_delay) == expected);
+  }
 
-    self.mix.process(input, filter_out, mix)
+  #[test]
+  fn should_keep_the_seed_when_reallocating_for_a_higher_sample_rate() {
+    let expected = render(&mut GrainDelay::with_seed(96000., 42));
+
+    let mut grain_delay = GrainDelay::with_seed(44100., 7);
+    grain_delay.reseed(42);
+    grain_delay.set_sample_rate(96000.);
+    assert!(render(&mut grain_delay) == expected);
+  }
+
+  #[test]
+  fn should_process_blocks_like_single_samples() {
+    let expected = render(&mut GrainDelay::with_seed(44100., 42));
+
+    let mut grain_delay = GrainDelay::with_seed(44100., 42);
+    let mut params = Params::new(44100.);
+    let input: Vec<f32> = (0..44100).map(|i| (i as f32 * 0.01).sin()).collect();
+    let mut left = vec![0.; 44100];
+    let mut right = vec![0.; 44100];
+    for ((input, left), right) in input
+      .chunks(128)
+      .zip(left.chunks_mut(128))
+      .zip(right.chunks_mut(128))
+    {
+      set_params(&mut params);
+      grain_delay.process_block(input, left, right, &mut params);
+    }
+
+    let output: Vec<(f32, f32)> = left.into_iter().zip(right).collect();
+    assert!(output == expected);
+  }
+
+  #[test]
+  fn should_apply_events_at_their_sample_offset() {
+    let input: Vec<f32> = (0..256).map(|i| (i as f32 * 0.01).sin()).collect();
+
+    let mut grain_delay = GrainDelay::with_seed(44100., 42);
+    let mut params = Params::new(44100.);
+    let mut expected = (vec![0.; 256], vec![0.; 256]);
+    set_params(&mut params);
+    grain_delay.process_block(
+      &input[..100],
+      &mut expected.0[..100],
+      &mut expected.1[..100],
+      &mut params,
+    );
+    set_params_with_mix(&mut params, 0.);
+    grain_delay.process_block(
+      &input[100..],
+      &mut expected.0[100..],
+      &mut expected.1[100..],
+      &mut params,
+    );
+
+    let mut grain_delay = GrainDelay::with_seed(44100., 42);
+    let mut params = Params::new(44100.);
+    let mut output = (vec![0.; 256], vec![0.; 256]);
+    set_params(&mut params);
+    grain_delay.process_block_with_events(
+      &input,
+      &mut output.0,
+      &mut output.1,
+      &mut params,
+      &[ParamEvent::new(100, 9, 0.)],
+      |event, params| set_params_with_mix(params, event.value),
+    );
+
+    assert!(output == expected);
   }
 
-  fn apply_feedback(&mut self, input: (f32, f32), feedback: f32) -> f32 {
-    let mono_input = (input.0 + input.1) * 0.5;
-    let feedback_out = mono_input * feedback;
-    self.dc_block.process(feedback_out.clamp(-1., 1.))
+  #[test]
+  fn should_glide_smoothly_in_tape_mode_with_the_default_quality() {
+    let mut grain_delay = GrainDelay::with_seed(44100., 42);
+    let mut params = Params::new(44100.);
+    let set_time = |params: &mut Params, time: f32| {
+      params.set(&ParamValues {
+        time,
+        time_mode: TimeMode::Tape,
+        pitch: 0.,
+        mix: 100.,
+        topology: Topology::PreDelay,
+        ..Default::default()
+      })
+    };
+    // A slow sine turns every uneven step of the read position into a bump in the output
+    let mut render = |params: &mut Params, range: std::ops::Range<usize>| -> Vec<f32> {
+      range
+        .map(|i| grain_delay.process((i as f32 * 0.001).sin(), params).0)
+        .collect()
+    };
+    set_time(&mut params, 100.);
+    render(&mut params, 0..44100);
+    set_time(&mut params, 150.);
+    let output = render(&mut params, 44100..48510);
+    // The glide starts with a kink, after which the read position should move smoothly
+    let max_curvature = output[500..]
+      .windows(3)
+      .map(|x| (x[0] - 2. * x[1] + x[2]).abs())
+      .fold(0., f32::max);
+    assert!(max_curvature < 1e-5);
+  }
+
+  #[test]
+  fn should_keep_playing_the_grains_when_frozen_before_the_delay() {
+    let mut grain_delay = GrainDelay::with_seed(44100., 42);
+    let mut params = Params::new(44100.);
+    let mut render = |freeze: bool, range: std::ops::Range<usize>| -> Vec<f32> {
+      params.set(&ParamValues {
+        freeze,
+        mix: 100.,
+        topology: Topology::PreDelay,
+        ..Default::default()
+      });
+      range
+        .map(|i| {
+          let input = if freeze { 0. } else { (i as f32 * 0.01).sin() };
+          grain_delay.process(input, &mut params).0
+        })
+        .collect()
+    };
+    render(false, 0..44100);
+    let output = render(true, 44100..132300);
+    let (min, max) = output[44100..]
+      .iter()
+      .fold((f32::MAX, f32::MIN), |(min, max), &x| {
+        (min.min(x), max.max(x))
+      });
+    assert!(max - min > 0.1);
+  }
+
+  #[test]
+  fn should_process_with_a_degenerate_config() {
+    let config = GrainDelayConfig {
+      max_delay_time: -1.,
+      max_pitch: f32::NAN,
+      max_spray: -1.,
+      min_frequency: 0.,
+      ..Default::default()
+    };
+    let mut grain_delay = GrainDelay::with_config_and_seed(44100., config, 42);
+    let mut params = Params::new(44100.);
+    params.set(&ParamValues::default());
+    assert!((0..1000)
+      .map(|i| grain_delay.process((i as f32 * 0.01).sin(), &mut params))
+      .all(|(left, right)| left.is_finite() && right.is_finite()));
+  }
+
+  #[test]
+  fn should_delay_the_dry_signal_by_the_latency() {
+    let mut grain_delay = GrainDelay::with_config_and_seed(
+      44100.,
+      GrainDelayConfig {
+        oversampling: Oversampling::Times2,
+        ..Default::default()
+      },
+      42,
+    );
+    let latency = grain_delay.latency();
+    assert_eq!(latency, 15);
+    assert_eq!(GrainDelay::with_seed(44100., 42).latency(), 0);
+
+    let mut params = Params::new(44100.);
+    set_params_with_mix(&mut params, 0.);
+    let input: Vec<f32> = (0..256).map(|i| (i as f32 * 0.01).sin()).collect();
+    let mut left = vec![0.; 256];
+    let mut right = vec![0.; 256];
+    grain_delay.process_block(&input, &mut left, &mut right, &mut params);
+    assert!(left[..latency].iter().all(|x| *x == 0.));
+    assert_eq!(left[latency..], input[..256 - latency]);
   }
 }
@@ -16,17 +16,16 @@ impl Mix {
     }
   }
 
-  pub fn process(&mut self, dry: f32, wet: (f32, f32), mix: f32) -> (f32, f32) {
+  pub fn process(&mut self, dry: (f32, f32), wet: (f32, f32), mix: f32) -> (f32, f32) {
     if mix != self.mix {
       let factor = mix * FRAC_PI_2;
       self.mix = mix;
       self.dry_gain = factor.fast_cos();
       self.wet_gain = factor.fast_sin();
     }
-    let dry_out = dry * self.dry_gain;
     (
-      dry_out + wet.0 * self.wet_gain,
-      dry_out + wet.1 * self.wet_gain,
+      dry.0 * self.dry_gain + wet.0 * self.wet_gain,
+      dry.1 * self.dry_gain + wet.1 * self.wet_gain,
     )
   }
 }
@@ -47,9 +46,9 @@ mod tests {
   #[test]
   fn mix() {
     let mut mix = Mix::new();
-    let first = mix.process(0., (1., 1.), 0.);
-    let second = mix.process(0., (1., 1.), 0.5);
-    let third = mix.process(0., (1., 1.), 1.);
+    let first = mix.process((0., 0.), (1., 1.), 0.);
+    let second = mix.process((0., 0.), (1., 1.), 0.5);
+    let third = mix.process((0., 0.), (1., 1.), 1.);
     assert_approximately_eq(first.0, 0., 3);
     assert_approximately_eq(first.1, 0., 3);
     assert_approximately_eq(second.0, 0.707, 3);
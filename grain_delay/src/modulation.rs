@@ -0,0 +1,118 @@
+use crate::{ParamId, MAX_DELAY_TIME};
+
+/// The number of samples between updates of the modulation sources.
+pub const CONTROL_INTERVAL: usize = 32;
+const DESTINATION_COUNT: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModDestination {
+  Pitch,
+  Spray,
+  Time,
+  Filter,
+  Spread,
+  Mix,
+  Frequency,
+  Feedback,
+}
+
+impl From<i32> for ModDestination {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::Spray,
+      2 => Self::Time,
+      3 => Self::Filter,
+      4 => Self::Spread,
+      5 => Self::Mix,
+      6 => Self::Frequency,
+      7 => Self::Feedback,
+      _ => Self::Pitch,
+    }
+  }
+}
+
+/// Sums the modulation per destination and ramps towards it over a control period.
+pub struct Modulation {
+  amounts: [f32; DESTINATION_COUNT],
+  steps: [f32; DESTINATION_COUNT],
+}
+
+impl Modulation {
+  pub fn new() -> Self {
+    Self {
+      amounts: [0.; DESTINATION_COUNT],
+      steps: [0.; DESTINATION_COUNT],
+    }
+  }
+
+  /// Takes bipolar amounts, where an amount of one is the full modulation range.
+  pub fn set_targets(&mut self, targets: impl Iterator<Item = (ModDestination, f32)>) {
+    let mut sums = [0.; DESTINATION_COUNT];
+    targets.for_each(|(destination, amount)| sums[destination as usize] += amount);
+    self
+      .steps
+      .iter_mut()
+      .zip(sums.iter().zip(self.amounts.iter()))
+      .for_each(|(step, (target, amount))| *step = (target - amount) / CONTROL_INTERVAL as f32);
+  }
+
+  pub fn next(&mut self) {
+    self
+      .amounts
+      .iter_mut()
+      .zip(self.steps.iter())
+      .for_each(|(amount, step)| *amount += step);
+  }
+
+  /// Modulates a processed param value. At full range the pitch moves an octave, spray and
+  /// frequency two octaves, time an octave and the filter four octaves. Spread, mix and feedback
+  /// move across their range.
+  pub fn apply(&self, destination: ModDestination, value: f32) -> f32 {
+    let amount = self.amounts[destination as usize];
+    match destination {
+      ModDestination::Pitch => value * amount.exp2(),
+      ModDestination::Spray => {
+        let descriptor = ParamId::Spray.descriptor();
+        (value * (amount * 2.).exp2()).clamp(descriptor.min, descriptor.max)
+      }
+      ModDestination::Time => (value * amount.exp2()).clamp(1., MAX_DELAY_TIME * 1000.),
+      ModDestination::Filter => {
+        let descriptor = ParamId::Filter.descriptor();
+        (value * (amount * 4.).exp2()).clamp(descriptor.min, descriptor.max)
+      }
+      ModDestination::Spread | ModDestination::Mix => (value + amount).clamp(0., 1.),
+      ModDestination::Frequency => {
+        let descriptor = ParamId::Frequency.descriptor();
+        (value * (amount * 2.).exp2()).clamp(descriptor.min, descriptor.max)
+      }
+      ModDestination::Feedback => {
+        (value + amount).clamp(0., ParamId::Feedback.descriptor().max * 0.01)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ModDestination, Modulation, CONTROL_INTERVAL};
+
+  #[test]
+  fn should_ramp_to_the_summed_targets() {
+    let mut modulation = Modulation::new();
+    modulation.set_targets(
+      [
+        (ModDestination::Mix, 0.25),
+        (ModDestination::Mix, 0.25),
+        (ModDestination::Filter, 0.5),
+      ]
+      .into_iter(),
+    );
+    modulation.next();
+    assert!(modulation.apply(ModDestination::Mix, 0.5) > 0.5);
+    (1..CONTROL_INTERVAL).for_each(|_| modulation.next());
+
+    assert!((modulation.apply(ModDestination::Mix, 0.5) - 1.).abs() < 1e-6);
+    assert!((modulation.apply(ModDestination::Filter, 1000.) - 4000.).abs() < 0.1);
+    assert_eq!(modulation.apply(ModDestination::Pitch, 2.), 2.);
+  }
+}
@@ -0,0 +1,197 @@
+mod half_band;
+use half_band::HalfBand;
+use std::collections::VecDeque;
+
+// The outer stage needs a steep filter, while the inner stage only has to remove the images above
+// the original sample rate.
+const STAGE_TAPS: [usize; 2] = [16, 8];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Oversampling {
+  Off,
+  Times2,
+  Times4,
+}
+
+impl Oversampling {
+  pub fn factor(&self) -> usize {
+    match self {
+      Self::Off => 1,
+      Self::Times2 => 2,
+      Self::Times4 => 4,
+    }
+  }
+
+  fn stage_count(&self) -> usize {
+    self.factor().trailing_zeros() as usize
+  }
+}
+
+/// A half-band stage that delays its input, so its latency is a whole number of samples at the
+/// original sample rate and the dry signal can line up exactly.
+struct Stage {
+  half_band: HalfBand,
+  padding: LatencyCompensation,
+  latency: usize,
+}
+
+impl Stage {
+  /// The factor is the oversampling factor at the output of this stage.
+  fn new(taps: usize, factor: usize) -> Self {
+    let half_band = HalfBand::new(taps);
+    // Each sample of padding at the input rate takes two samples at the output rate
+    let padding = (factor - half_band.latency() % factor) % factor / 2;
+    Self {
+      latency: (half_band.latency() + padding * 2) / factor,
+      half_band,
+      padding: LatencyCompensation::new(padding),
+    }
+  }
+}
+
+/// Runs a process at a multiple of the sample rate.
+pub struct Oversampler {
+  stages: Vec<Stage>,
+}
+
+impl Oversampler {
+  pub fn new(oversampling: Oversampling) -> Self {
+    Self {
+      stages: STAGE_TAPS[..oversampling.stage_count()]
+        .iter()
+        .enumerate()
+        .map(|(i, taps)| Stage::new(*taps, 2 << i))
+        .collect(),
+    }
+  }
+
+  /// Returns the latency in samples at the original sample rate.
+  pub fn latency(&self) -> usize {
+    self.stages.iter().map(|stage| stage.latency).sum()
+  }
+
+  pub fn reset(&mut self) {
+    self.stages.iter_mut().for_each(|stage| {
+      stage.half_band.reset();
+      stage.padding.reset();
+    });
+  }
+
+  pub fn process(
+    &mut self,
+    input: (f32, f32),
+    mut process: impl FnMut((f32, f32)) -> (f32, f32),
+  ) -> (f32, f32) {
+    Self::process_stages(&mut self.stages, input, &mut process)
+  }
+
+  fn process_stages(
+    stages: &mut [Stage],
+    input: (f32, f32),
+    process: &mut impl FnMut((f32, f32)) -> (f32, f32),
+  ) -> (f32, f32) {
+    match stages.split_first_mut() {
+      Some((stage, inner_stages)) => {
+        let upsampled = stage.half_band.upsample(stage.padding.process(input));
+        let processed = upsampled.map(|sample| Self::process_stages(inner_stages, sample, process));
+        stage.half_band.downsample(processed)
+      }
+      None => process(input),
+    }
+  }
+}
+
+/// Delays a signal by the latency of the oversampler, so it lines up with the oversampled signal.
+pub struct LatencyCompensation {
+  buffer: VecDeque<(f32, f32)>,
+}
+
+impl LatencyCompensation {
+  pub fn new(latency: usize) -> Self {
+    Self {
+      buffer: VecDeque::from(vec![(0., 0.); latency]),
+    }
+  }
+
+  pub fn reset(&mut self) {
+    self.buffer.iter_mut().for_each(|sample| *sample = (0., 0.));
+  }
+
+  pub fn process(&mut self, input: (f32, f32)) -> (f32, f32) {
+    match self.buffer.pop_front() {
+      Some(output) => {
+        self.buffer.push_back(input);
+        output
+      }
+      None => input,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{LatencyCompensation, Oversampler, Oversampling};
+
+  fn get_impulse_response(oversampling: Oversampling) -> (usize, Vec<f32>) {
+    let mut oversampler = Oversampler::new(oversampling);
+    let response = (0..64)
+      .map(|i| {
+        let input = if i == 0 { 1. } else { 0. };
+        oversampler.process((input, input), |sample| sample).0
+      })
+      .collect();
+    (oversampler.latency(), response)
+  }
+
+  #[test]
+  fn should_pass_the_signal_with_latency() {
+    assert_eq!(
+      get_impulse_response(Oversampling::Off),
+      (0, {
+        let mut response = vec![0.; 64];
+        response[0] = 1.;
+        response
+      })
+    );
+    for (oversampling, expected_latency) in [(Oversampling::Times2, 15), (Oversampling::Times4, 19)]
+    {
+      let (latency, response) = get_impulse_response(oversampling);
+      assert_eq!(latency, expected_latency);
+      let peak = response
+        .iter()
+        .enumerate()
+        .fold(0, |peak, (i, x)| if *x > response[peak] { i } else { peak });
+      assert!(peak.abs_diff(latency) <= 1);
+      let sum = response.iter().sum::<f32>();
+      assert!((sum - 1.).abs() < 1e-3);
+      // The filters are symmetric, so the center of the response is the exact latency
+      let center = response
+        .iter()
+        .enumerate()
+        .map(|(i, x)| i as f32 * x)
+        .sum::<f32>()
+        / sum;
+      assert!((center - latency as f32).abs() < 1e-3);
+    }
+  }
+
+  #[test]
+  fn should_run_the_process_at_the_oversampled_rate() {
+    let mut oversampler = Oversampler::new(Oversampling::Times4);
+    let mut count = 0;
+    oversampler.process((0., 0.), |sample| {
+      count += 1;
+      sample
+    });
+    assert_eq!(count, 4);
+  }
+
+  #[test]
+  fn should_delay_by_the_latency() {
+    let mut latency_compensation = LatencyCompensation::new(2);
+    assert_eq!(latency_compensation.process((1., 1.)), (0., 0.));
+    assert_eq!(latency_compensation.process((2., 2.)), (0., 0.));
+    assert_eq!(latency_compensation.process((3., 3.)), (1., 1.));
+    assert_eq!(LatencyCompensation::new(0).process((1., 1.)), (1., 1.));
+  }
+}
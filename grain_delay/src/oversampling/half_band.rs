@@ -0,0 +1,83 @@
+use std::{
+  collections::VecDeque,
+  f32::consts::{PI, TAU},
+};
+
+/// A polyphase half-band filter, which doubles or halves the sample rate of a stereo signal.
+/// Half of the taps of a half-band filter are zero, except for the center tap, so one phase is a
+/// plain delay and the other phase only needs the even taps.
+pub struct HalfBand {
+  taps: Vec<f32>,
+  upsample_history: VecDeque<(f32, f32)>,
+  downsample_history: VecDeque<(f32, f32)>,
+  odd_history: VecDeque<(f32, f32)>,
+}
+
+impl HalfBand {
+  /// The number of even taps should be a multiple of two.
+  pub fn new(even_taps: usize) -> Self {
+    let length = even_taps * 2 - 1;
+    let center = (length / 2) as f32;
+    let mut taps: Vec<f32> = (0..even_taps)
+      .map(|i| {
+        let x = (i * 2) as f32 - center;
+        let sinc = (PI * x * 0.5).sin() / (PI * x * 0.5);
+        // Blackman window
+        let phase = TAU * (i * 2) as f32 / (length - 1) as f32;
+        sinc * (0.42 - 0.5 * phase.cos() + 0.08 * (2. * phase).cos())
+      })
+      .collect();
+    // Each phase should have unity gain at DC
+    let sum: f32 = taps.iter().sum();
+    taps.iter_mut().for_each(|tap| *tap /= sum);
+
+    Self {
+      upsample_history: VecDeque::from(vec![(0., 0.); even_taps]),
+      downsample_history: VecDeque::from(vec![(0., 0.); even_taps]),
+      odd_history: VecDeque::from(vec![(0., 0.); even_taps / 2]),
+      taps,
+    }
+  }
+
+  /// Returns the latency of upsampling and downsampling in samples at the higher sample rate.
+  pub fn latency(&self) -> usize {
+    self.taps.len() * 2 - 2
+  }
+
+  pub fn reset(&mut self) {
+    [
+      &mut self.upsample_history,
+      &mut self.downsample_history,
+      &mut self.odd_history,
+    ]
+    .into_iter()
+    .flat_map(|history| history.iter_mut())
+    .for_each(|sample| *sample = (0., 0.));
+  }
+
+  pub fn upsample(&mut self, input: (f32, f32)) -> [(f32, f32); 2] {
+    self.upsample_history.pop_back();
+    self.upsample_history.push_front(input);
+    let even = Self::convolve(&self.taps, &self.upsample_history);
+    let odd = self.upsample_history[self.taps.len() / 2 - 1];
+    [even, odd]
+  }
+
+  pub fn downsample(&mut self, input: [(f32, f32); 2]) -> (f32, f32) {
+    self.downsample_history.pop_back();
+    self.downsample_history.push_front(input[0]);
+    let even = Self::convolve(&self.taps, &self.downsample_history);
+    let odd = self.odd_history.pop_back().unwrap_or_default();
+    self.odd_history.push_front(input[1]);
+    ((even.0 + odd.0) * 0.5, (even.1 + odd.1) * 0.5)
+  }
+
+  fn convolve(taps: &[f32], history: &VecDeque<(f32, f32)>) -> (f32, f32) {
+    taps
+      .iter()
+      .zip(history)
+      .fold((0., 0.), |sum, (tap, sample)| {
+        (sum.0 + sample.0 * tap, sum.1 + sample.1 * tap)
+      })
+  }
+}
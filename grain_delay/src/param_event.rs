@@ -0,0 +1,64 @@
+use std::{iter, ops::Range};
+
+/// A parameter change that takes effect at a sample offset within a block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParamEvent {
+  pub offset: usize,
+  pub index: usize,
+  pub value: f32,
+}
+
+impl ParamEvent {
+  pub fn new(offset: usize, index: usize, value: f32) -> Self {
+    Self {
+      offset,
+      index,
+      value,
+    }
+  }
+}
+
+/// Splits a block into the ranges in between events, each followed by the event that ends it.
+/// Offsets beyond the block or before a previous event are clamped.
+pub fn segments(
+  length: usize,
+  events: &[ParamEvent],
+) -> impl Iterator<Item = (Range<usize>, Option<&ParamEvent>)> {
+  let mut start = 0;
+  events
+    .iter()
+    .map(Some)
+    .chain(iter::once(None))
+    .map(move |event| {
+      let end = event.map_or(length, |event| event.offset.clamp(start, length));
+      let range = start..end;
+      start = end;
+      (range, event)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{segments, ParamEvent};
+
+  #[test]
+  fn should_split_a_block_at_the_event_offsets() {
+    let events = [
+      ParamEvent::new(10, 0, 1.),
+      ParamEvent::new(5, 1, 2.),
+      ParamEvent::new(80, 2, 3.),
+    ];
+    let ranges: Vec<_> = segments(64, &events)
+      .map(|(range, event)| (range, event.map(|event| event.index)))
+      .collect();
+    assert_eq!(
+      ranges,
+      vec![
+        (0..10, Some(0)),
+        (10..10, Some(1)),
+        (10..64, Some(2)),
+        (64..64, None)
+      ]
+    );
+  }
+}
@@ -1,6 +1,35 @@
+mod beat_division;
+mod descriptor;
+mod param_values;
 mod smooth;
-use smooth::LinearSmooth;
-pub use {crate::MAX_DRIFT, smooth::Smoother};
+use crate::{
+  feedback::FeedbackMode,
+  grains::{PanMode, SprayDistribution, WindowShape, DEFAULT_VOICES, MAX_VOICES, MIN_VOICES},
+  lfo::{LfoShape, LFO_COUNT},
+  modulation::ModDestination,
+  saturation::{SaturationCurve, MAX_DRIVE},
+  shared::delay_line::InterpolationQuality,
+  state_variable_filter::FilterMode,
+  topology::Topology,
+  variable_delay_line::TimeMode,
+  MAX_DELAY_TIME, MIN_FREQUENCY,
+};
+pub(crate) use smooth::LinearSmooth;
+pub use {
+  crate::MAX_DRIFT,
+  beat_division::BeatDivision,
+  descriptor::{ParamDescriptor, ParamId, ParamKind, Unit, PARAMS, PARAM_COUNT},
+  param_values::{ParamError, ParamValues},
+  smooth::Smoother,
+};
+
+#[derive(Clone, Copy)]
+pub struct LfoParams {
+  pub shape: LfoShape,
+  pub freq: f32,
+  pub depth: f32,
+  pub destination: ModDestination,
+}
 
 pub struct Params {
   pub spray: f32,
@@ -13,7 +42,34 @@ pub struct Params {
   pub filter: LinearSmooth,
   pub spread: f32,
   pub mix: LinearSmooth,
-  is_initialized: bool,
+  pub voices: usize,
+  pub window_shape: WindowShape,
+  pub taper: f32,
+  pub feedback_mode: FeedbackMode,
+  pub cross_feedback: LinearSmooth,
+  pub freeze: bool,
+  pub filter_mode: FilterMode,
+  pub resonance: LinearSmooth,
+  pub saturation_curve: SaturationCurve,
+  pub drive: LinearSmooth,
+  pub duck_threshold: f32,
+  pub duck_amount: LinearSmooth,
+  pub duck_attack: f32,
+  pub duck_release: f32,
+  pub lfos: [LfoParams; LFO_COUNT],
+  pub envelope_attack: f32,
+  pub envelope_release: f32,
+  pub envelope_depth: f32,
+  pub envelope_destination: ModDestination,
+  pub topology: Topology,
+  pub diffusion: LinearSmooth,
+  pub spray_distribution: SprayDistribution,
+  pub pan_mode: PanMode,
+  pub interpolation_quality: InterpolationQuality,
+  pub time_mode: TimeMode,
+  pub time_fade: f32,
+  /// The last values that were applied, which are applied again after a sample rate change.
+  values: Option<ParamValues>,
 }
 
 impl Params {
@@ -29,42 +85,207 @@ impl Params {
       filter: LinearSmooth::new(sample_rate, 12.),
       spread: 0.,
       mix: LinearSmooth::new(sample_rate, 12.),
-      is_initialized: false,
+      voices: DEFAULT_VOICES,
+      window_shape: WindowShape::Hann,
+      taper: 0.,
+      feedback_mode: FeedbackMode::Mono,
+      cross_feedback: LinearSmooth::new(sample_rate, 12.),
+      freeze: false,
+      filter_mode: FilterMode::LowPass,
+      resonance: LinearSmooth::new(sample_rate, 12.),
+      saturation_curve: SaturationCurve::Clip,
+      drive: LinearSmooth::new(sample_rate, 12.),
+      duck_threshold: 0.,
+      duck_amount: LinearSmooth::new(sample_rate, 12.),
+      duck_attack: 0.,
+      duck_release: 0.,
+      lfos: [LfoParams {
+        shape: LfoShape::Sine,
+        freq: 0.,
+        depth: 0.,
+        destination: ModDestination::Pitch,
+      }; LFO_COUNT],
+      envelope_attack: 0.,
+      envelope_release: 0.,
+      envelope_depth: 0.,
+      envelope_destination: ModDestination::Pitch,
+      topology: Topology::InLoop,
+      diffusion: LinearSmooth::new(sample_rate, 12.),
+      spray_distribution: SprayDistribution::Uniform,
+      pan_mode: PanMode::Random,
+      interpolation_quality: InterpolationQuality::Normal,
+      time_mode: TimeMode::Crossfade,
+      time_fade: 0.,
+      values: None,
+    }
+  }
+
+  /// Adapts the smoothing to the sample rate. The smoothed params jump to their target.
+  pub fn set_sample_rate(&mut self, sample_rate: f32) {
+    let values = self.values;
+    *self = Self::new(sample_rate);
+    if let Some(values) = values {
+      self.apply(values);
     }
   }
 
-  pub fn set(
-    &mut self,
-    spray: f32,
-    freq: f32,
-    pitch: f32,
-    drift: f32,
-    reverse: f32,
-    time: f32,
-    feedback: f32,
-    filter: f32,
-    spread: f32,
-    mix: f32,
-  ) {
+  /// Sets the params after clamping the values to their range.
+  pub fn set(&mut self, values: &ParamValues) {
+    self.apply(values.clamped());
+  }
+
+  /// Sets the params, or returns an error without changing them when a value is out of range.
+  pub fn try_set(&mut self, values: &ParamValues) -> Result<(), ParamError> {
+    values.validate()?;
+    self.apply(*values);
+    Ok(())
+  }
+
+  fn apply(&mut self, values: ParamValues) {
+    let ParamValues {
+      spray,
+      frequency,
+      pitch,
+      drift,
+      reverse,
+      time,
+      feedback,
+      filter,
+      spread,
+      mix,
+      voices,
+      window_shape,
+      taper,
+      feedback_mode,
+      cross_feedback,
+      freeze,
+      time_division,
+      frequency_division,
+      filter_mode,
+      resonance,
+      saturation,
+      drive,
+      duck_threshold,
+      duck_amount,
+      duck_attack,
+      duck_release,
+      lfo1_shape,
+      lfo1_rate,
+      lfo1_sync,
+      lfo1_depth,
+      lfo1_destination,
+      lfo2_shape,
+      lfo2_rate,
+      lfo2_sync,
+      lfo2_depth,
+      lfo2_destination,
+      envelope_attack,
+      envelope_release,
+      envelope_depth,
+      envelope_destination,
+      topology,
+      diffusion,
+      spray_distribution,
+      pan_mode,
+      interpolation_quality,
+      time_mode,
+      time_fade,
+      tempo,
+    } = values;
+    let drive = 1. + drive * 0.01 * (MAX_DRIVE - 1.);
+    let duck_amount = duck_amount * 0.01;
+    let diffusion = diffusion * 0.01;
+    let (drift, reverse, feedback, spread, mix, taper, cross_feedback, resonance) = (
+      drift * 0.01,
+      reverse * 0.01,
+      feedback * 0.01,
+      spread * 0.01,
+      mix * 0.01,
+      taper * 0.01,
+      cross_feedback * 0.01,
+      resonance * 0.01,
+    );
+
     self.spray = spray;
-    self.freq = freq;
+    self.freq = match frequency_division.to_milliseconds(tempo) {
+      Some(period) => (1000. / period).max(MIN_FREQUENCY),
+      None => frequency,
+    };
     self.drift = drift * drift * MAX_DRIFT;
     self.reverse = reverse;
-    self.time = time;
+    self.time = match time_division.to_milliseconds(tempo) {
+      Some(time) => time.min(MAX_DELAY_TIME * 1000.),
+      None => time,
+    };
     self.spread = spread;
+    self.voices = voices.clamp(MIN_VOICES, MAX_VOICES);
+    self.window_shape = window_shape;
+    self.taper = taper;
+    self.feedback_mode = feedback_mode;
+    self.freeze = freeze;
+    self.filter_mode = filter_mode;
+    self.saturation_curve = saturation;
+    self.duck_threshold = duck_threshold;
+    self.duck_attack = duck_attack;
+    self.duck_release = duck_release;
+    self.lfos = [
+      (
+        lfo1_shape,
+        lfo1_rate,
+        lfo1_sync,
+        lfo1_depth,
+        lfo1_destination,
+      ),
+      (
+        lfo2_shape,
+        lfo2_rate,
+        lfo2_sync,
+        lfo2_depth,
+        lfo2_destination,
+      ),
+    ]
+    .map(|(shape, rate, sync, depth, destination)| LfoParams {
+      shape,
+      freq: match sync.to_milliseconds(tempo) {
+        Some(period) => 1000. / period,
+        None => rate,
+      },
+      depth: depth * 0.01,
+      destination,
+    });
+    self.envelope_attack = envelope_attack;
+    self.envelope_release = envelope_release;
+    self.envelope_depth = envelope_depth * 0.01;
+    self.envelope_destination = envelope_destination;
+    self.topology = topology;
+    self.spray_distribution = spray_distribution;
+    self.pan_mode = pan_mode;
+    self.interpolation_quality = interpolation_quality;
+    self.time_mode = time_mode;
+    self.time_fade = time_fade;
 
     let speed = 2_f32.powf(pitch / 12.);
-    if self.is_initialized {
+    if self.values.is_some() {
       self.speed.set_target(speed);
       self.feedback.set_target(feedback);
       self.filter.set_target(filter);
       self.mix.set_target(mix);
+      self.cross_feedback.set_target(cross_feedback);
+      self.resonance.set_target(resonance);
+      self.drive.set_target(drive);
+      self.duck_amount.set_target(duck_amount);
+      self.diffusion.set_target(diffusion);
     } else {
       self.speed.reset(speed);
       self.feedback.reset(feedback);
       self.filter.reset(filter);
       self.mix.reset(mix);
-      self.is_initialized = true;
+      self.cross_feedback.reset(cross_feedback);
+      self.resonance.reset(resonance);
+      self.drive.reset(drive);
+      self.duck_amount.reset(duck_amount);
+      self.diffusion.reset(diffusion);
     }
+    self.values = Some(values);
   }
 }
@@ -0,0 +1,84 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BeatDivision {
+  Free,
+  Whole,
+  Half,
+  HalfDotted,
+  HalfTriplet,
+  Quarter,
+  QuarterDotted,
+  QuarterTriplet,
+  Eighth,
+  EighthDotted,
+  EighthTriplet,
+  Sixteenth,
+  SixteenthDotted,
+  SixteenthTriplet,
+  ThirtySecond,
+}
+
+impl From<i32> for BeatDivision {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::Whole,
+      2 => Self::Half,
+      3 => Self::HalfDotted,
+      4 => Self::HalfTriplet,
+      5 => Self::Quarter,
+      6 => Self::QuarterDotted,
+      7 => Self::QuarterTriplet,
+      8 => Self::Eighth,
+      9 => Self::EighthDotted,
+      10 => Self::EighthTriplet,
+      11 => Self::Sixteenth,
+      12 => Self::SixteenthDotted,
+      13 => Self::SixteenthTriplet,
+      14 => Self::ThirtySecond,
+      _ => Self::Free,
+    }
+  }
+}
+
+impl BeatDivision {
+  /// Returns the duration in milliseconds, or None when the division isn't synced to the tempo.
+  pub fn to_milliseconds(&self, tempo: f32) -> Option<f32> {
+    let beats = match self {
+      Self::Free => return None,
+      Self::Whole => 4.,
+      Self::Half => 2.,
+      Self::HalfDotted => 3.,
+      Self::HalfTriplet => 4. / 3.,
+      Self::Quarter => 1.,
+      Self::QuarterDotted => 1.5,
+      Self::QuarterTriplet => 2. / 3.,
+      Self::Eighth => 0.5,
+      Self::EighthDotted => 0.75,
+      Self::EighthTriplet => 1. / 3.,
+      Self::Sixteenth => 0.25,
+      Self::SixteenthDotted => 0.375,
+      Self::SixteenthTriplet => 1. / 6.,
+      Self::ThirtySecond => 0.125,
+    };
+    Some(beats * 60000. / tempo)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::BeatDivision;
+
+  #[test]
+  fn should_convert_divisions_to_milliseconds() {
+    assert_eq!(BeatDivision::Free.to_milliseconds(120.), None);
+    assert_eq!(BeatDivision::Quarter.to_milliseconds(120.), Some(500.));
+    assert_eq!(
+      BeatDivision::QuarterDotted.to_milliseconds(120.),
+      Some(750.)
+    );
+    assert_eq!(
+      BeatDivision::EighthTriplet.to_milliseconds(100.),
+      Some(200.)
+    );
+    assert_eq!(BeatDivision::Whole.to_milliseconds(60.), Some(4000.));
+  }
+}
@@ -0,0 +1,617 @@
+pub const PARAM_COUNT: usize = 47;
+
+const WINDOW_SHAPES: [&str; 6] = [
+  "Hann",
+  "Tukey",
+  "Gaussian",
+  "Trapezoid",
+  "Expodec",
+  "Rexpodec",
+];
+const FEEDBACK_MODES: [&str; 3] = ["Mono", "Stereo", "Ping pong"];
+const FILTER_MODES: [&str; 4] = ["Low pass", "High pass", "Band pass", "Notch"];
+const SATURATION_CURVES: [&str; 5] = ["Clip", "Tanh", "Fast tanh", "Rational tanh", "Atan"];
+const LFO_SHAPES: [&str; 5] = [
+  "Sine",
+  "Triangle",
+  "Square",
+  "Sample & hold",
+  "Smooth random",
+];
+const MOD_DESTINATIONS: [&str; 8] = [
+  "Pitch",
+  "Spray",
+  "Time",
+  "Filter",
+  "Spread",
+  "Mix",
+  "Frequency",
+  "Feedback",
+];
+const TOPOLOGIES: [&str; 3] = ["Grains in loop", "Grains post delay", "Grains pre delay"];
+const SPRAY_DISTRIBUTIONS: [&str; 5] = ["Uniform", "Gaussian", "Exponential", "Quantized", "Walk"];
+const PAN_MODES: [&str; 5] = ["Random", "Alternating", "Rotating", "Pitch", "Fixed voices"];
+const INTERPOLATION_QUALITIES: [&str; 3] = ["Eco", "Normal", "High"];
+const TIME_MODES: [&str; 3] = ["Crossfade", "Tape", "Jump"];
+const BEAT_DIVISIONS: [&str; 15] = [
+  "Free",
+  "1/1",
+  "1/2",
+  "1/2 dotted",
+  "1/2 triplet",
+  "1/4",
+  "1/4 dotted",
+  "1/4 triplet",
+  "1/8",
+  "1/8 dotted",
+  "1/8 triplet",
+  "1/16",
+  "1/16 dotted",
+  "1/16 triplet",
+  "1/32",
+];
+
+/// Identifies a parameter. The order matches the parameter indices of the plugins.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamId {
+  Spray,
+  Frequency,
+  Pitch,
+  Drift,
+  Reverse,
+  Time,
+  Feedback,
+  Filter,
+  Spread,
+  Mix,
+  Voices,
+  WindowShape,
+  Taper,
+  FeedbackMode,
+  CrossFeedback,
+  Freeze,
+  TimeDivision,
+  FrequencyDivision,
+  FilterMode,
+  Resonance,
+  Saturation,
+  Drive,
+  DuckThreshold,
+  DuckAmount,
+  DuckAttack,
+  DuckRelease,
+  Lfo1Shape,
+  Lfo1Rate,
+  Lfo1Sync,
+  Lfo1Depth,
+  Lfo1Destination,
+  Lfo2Shape,
+  Lfo2Rate,
+  Lfo2Sync,
+  Lfo2Depth,
+  Lfo2Destination,
+  EnvelopeAttack,
+  EnvelopeRelease,
+  EnvelopeDepth,
+  EnvelopeDestination,
+  Topology,
+  Diffusion,
+  SprayDistribution,
+  PanMode,
+  InterpolationQuality,
+  TimeMode,
+  TimeFade,
+}
+
+impl ParamId {
+  pub fn descriptor(self) -> &'static ParamDescriptor {
+    &PARAMS[self as usize]
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unit {
+  None,
+  Milliseconds,
+  Hertz,
+  Semitones,
+  Percent,
+  Decibels,
+}
+
+impl Unit {
+  fn suffix(&self) -> &'static str {
+    match self {
+      Self::None => "",
+      Self::Milliseconds => " ms",
+      Self::Hertz => " hz",
+      Self::Semitones => " st",
+      Self::Percent => " %",
+      Self::Decibels => " dB",
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamKind {
+  Float,
+  Integer,
+  Toggle,
+  Enumeration(&'static [&'static str]),
+}
+
+/// Describes the range and presentation of a parameter. Values are in the unit of the descriptor,
+/// so percentages range from 0 to 100.
+#[derive(Debug, PartialEq)]
+pub struct ParamDescriptor {
+  pub id: ParamId,
+  pub symbol: &'static str,
+  pub name: &'static str,
+  pub unit: Unit,
+  pub kind: ParamKind,
+  pub min: f32,
+  pub max: f32,
+  pub default: f32,
+  /// The exponent of the curve that maps a normalized value onto the range.
+  pub skew: f32,
+}
+
+impl ParamDescriptor {
+  /// Maps a value onto the 0 to 1 range.
+  pub fn normalize(&self, value: f32) -> f32 {
+    ((value.clamp(self.min, self.max) - self.min) / (self.max - self.min)).powf(self.skew.recip())
+  }
+
+  /// Maps a 0 to 1 value onto the range of the parameter.
+  pub fn denormalize(&self, normalized: f32) -> f32 {
+    let value = self.min + normalized.clamp(0., 1.).powf(self.skew) * (self.max - self.min);
+    match self.kind {
+      ParamKind::Float => value,
+      _ => value.round(),
+    }
+  }
+
+  pub fn format(&self, value: f32) -> String {
+    match self.kind {
+      ParamKind::Float => format!("{:.2}{}", value, self.unit.suffix()),
+      ParamKind::Integer => format!("{}{}", value as i32, self.unit.suffix()),
+      ParamKind::Toggle => if value == 1. { "On" } else { "Off" }.to_string(),
+      ParamKind::Enumeration(labels) => {
+        labels[(value.max(0.) as usize).min(labels.len() - 1)].to_string()
+      }
+    }
+  }
+}
+
+const fn float(
+  id: ParamId,
+  symbol: &'static str,
+  name: &'static str,
+  unit: Unit,
+  range: (f32, f32),
+  default: f32,
+  skew: f32,
+) -> ParamDescriptor {
+  ParamDescriptor {
+    id,
+    symbol,
+    name,
+    unit,
+    kind: ParamKind::Float,
+    min: range.0,
+    max: range.1,
+    default,
+    skew,
+  }
+}
+
+const fn enumeration(
+  id: ParamId,
+  symbol: &'static str,
+  name: &'static str,
+  labels: &'static [&'static str],
+) -> ParamDescriptor {
+  ParamDescriptor {
+    id,
+    symbol,
+    name,
+    unit: Unit::None,
+    kind: ParamKind::Enumeration(labels),
+    min: 0.,
+    max: (labels.len() - 1) as f32,
+    default: 0.,
+    skew: 1.,
+  }
+}
+
+pub static PARAMS: [ParamDescriptor; PARAM_COUNT] = [
+  float(
+    ParamId::Spray,
+    "spray",
+    "Spray",
+    Unit::Milliseconds,
+    (1., 500.),
+    2.,
+    3.,
+  ),
+  float(
+    ParamId::Frequency,
+    "frequency",
+    "Frequency",
+    Unit::Hertz,
+    (1., 150.),
+    5.,
+    3.,
+  ),
+  float(
+    ParamId::Pitch,
+    "pitch",
+    "Pitch",
+    Unit::Semitones,
+    (-24., 24.),
+    12.,
+    1.,
+  ),
+  float(
+    ParamId::Drift,
+    "drift",
+    "Drift",
+    Unit::Percent,
+    (0., 100.),
+    0.,
+    1.,
+  ),
+  float(
+    ParamId::Reverse,
+    "reverse",
+    "Reverse",
+    Unit::Percent,
+    (0., 100.),
+    0.,
+    1.,
+  ),
+  // The range is part of the saved state of the plugins, so it stays at 5 seconds. Longer delays
+  // of the config are reached with the tempo sync and the modulation.
+  float(
+    ParamId::Time,
+    "time",
+    "Time",
+    Unit::Milliseconds,
+    (1., 5000.),
+    1.,
+    3.,
+  ),
+  float(
+    ParamId::Feedback,
+    "feedback",
+    "Feedback",
+    Unit::Percent,
+    (0., 150.),
+    50.,
+    1.,
+  ),
+  float(
+    ParamId::Filter,
+    "filter",
+    "Filter",
+    Unit::Hertz,
+    (20., 11025.),
+    5000.,
+    3.,
+  ),
+  float(
+    ParamId::Spread,
+    "spread",
+    "Spread",
+    Unit::Percent,
+    (0., 100.),
+    0.,
+    1.,
+  ),
+  float(
+    ParamId::Mix,
+    "mix",
+    "Mix",
+    Unit::Percent,
+    (0., 100.),
+    50.,
+    1.,
+  ),
+  ParamDescriptor {
+    id: ParamId::Voices,
+    symbol: "voices",
+    name: "Voices",
+    unit: Unit::None,
+    kind: ParamKind::Integer,
+    min: 1.,
+    max: 32.,
+    default: 4.,
+    skew: 1.,
+  },
+  enumeration(
+    ParamId::WindowShape,
+    "window_shape",
+    "Window",
+    &WINDOW_SHAPES,
+  ),
+  float(
+    ParamId::Taper,
+    "taper",
+    "Taper",
+    Unit::Percent,
+    (0., 100.),
+    50.,
+    1.,
+  ),
+  enumeration(
+    ParamId::FeedbackMode,
+    "feedback_mode",
+    "Feedback mode",
+    &FEEDBACK_MODES,
+  ),
+  float(
+    ParamId::CrossFeedback,
+    "cross_feedback",
+    "Cross feedback",
+    Unit::Percent,
+    (0., 100.),
+    0.,
+    1.,
+  ),
+  ParamDescriptor {
+    id: ParamId::Freeze,
+    symbol: "freeze",
+    name: "Freeze",
+    unit: Unit::None,
+    kind: ParamKind::Toggle,
+    min: 0.,
+    max: 1.,
+    default: 0.,
+    skew: 1.,
+  },
+  enumeration(
+    ParamId::TimeDivision,
+    "time_division",
+    "Time sync",
+    &BEAT_DIVISIONS,
+  ),
+  enumeration(
+    ParamId::FrequencyDivision,
+    "frequency_division",
+    "Frequency sync",
+    &BEAT_DIVISIONS,
+  ),
+  enumeration(
+    ParamId::FilterMode,
+    "filter_mode",
+    "Filter mode",
+    &FILTER_MODES,
+  ),
+  float(
+    ParamId::Resonance,
+    "resonance",
+    "Resonance",
+    Unit::Percent,
+    (0., 100.),
+    0.,
+    1.,
+  ),
+  enumeration(
+    ParamId::Saturation,
+    "saturation",
+    "Saturation",
+    &SATURATION_CURVES,
+  ),
+  float(
+    ParamId::Drive,
+    "drive",
+    "Drive",
+    Unit::Percent,
+    (0., 100.),
+    0.,
+    1.,
+  ),
+  float(
+    ParamId::DuckThreshold,
+    "duck_threshold",
+    "Duck threshold",
+    Unit::Decibels,
+    (-60., 0.),
+    -30.,
+    1.,
+  ),
+  float(
+    ParamId::DuckAmount,
+    "duck_amount",
+    "Duck amount",
+    Unit::Percent,
+    (0., 100.),
+    0.,
+    1.,
+  ),
+  float(
+    ParamId::DuckAttack,
+    "duck_attack",
+    "Duck attack",
+    Unit::Milliseconds,
+    (0.1, 100.),
+    5.,
+    3.,
+  ),
+  float(
+    ParamId::DuckRelease,
+    "duck_release",
+    "Duck release",
+    Unit::Milliseconds,
+    (10., 2000.),
+    250.,
+    3.,
+  ),
+  enumeration(ParamId::Lfo1Shape, "lfo1_shape", "LFO 1 shape", &LFO_SHAPES),
+  float(
+    ParamId::Lfo1Rate,
+    "lfo1_rate",
+    "LFO 1 rate",
+    Unit::Hertz,
+    (0.05, 20.),
+    1.,
+    3.,
+  ),
+  enumeration(
+    ParamId::Lfo1Sync,
+    "lfo1_sync",
+    "LFO 1 sync",
+    &BEAT_DIVISIONS,
+  ),
+  float(
+    ParamId::Lfo1Depth,
+    "lfo1_depth",
+    "LFO 1 depth",
+    Unit::Percent,
+    (0., 100.),
+    0.,
+    1.,
+  ),
+  enumeration(
+    ParamId::Lfo1Destination,
+    "lfo1_destination",
+    "LFO 1 destination",
+    &MOD_DESTINATIONS,
+  ),
+  enumeration(ParamId::Lfo2Shape, "lfo2_shape", "LFO 2 shape", &LFO_SHAPES),
+  float(
+    ParamId::Lfo2Rate,
+    "lfo2_rate",
+    "LFO 2 rate",
+    Unit::Hertz,
+    (0.05, 20.),
+    1.,
+    3.,
+  ),
+  enumeration(
+    ParamId::Lfo2Sync,
+    "lfo2_sync",
+    "LFO 2 sync",
+    &BEAT_DIVISIONS,
+  ),
+  float(
+    ParamId::Lfo2Depth,
+    "lfo2_depth",
+    "LFO 2 depth",
+    Unit::Percent,
+    (0., 100.),
+    0.,
+    1.,
+  ),
+  enumeration(
+    ParamId::Lfo2Destination,
+    "lfo2_destination",
+    "LFO 2 destination",
+    &MOD_DESTINATIONS,
+  ),
+  float(
+    ParamId::EnvelopeAttack,
+    "envelope_attack",
+    "Envelope attack",
+    Unit::Milliseconds,
+    (0.1, 100.),
+    10.,
+    3.,
+  ),
+  float(
+    ParamId::EnvelopeRelease,
+    "envelope_release",
+    "Envelope release",
+    Unit::Milliseconds,
+    (10., 2000.),
+    200.,
+    3.,
+  ),
+  float(
+    ParamId::EnvelopeDepth,
+    "envelope_depth",
+    "Envelope depth",
+    Unit::Percent,
+    (-100., 100.),
+    0.,
+    1.,
+  ),
+  enumeration(
+    ParamId::EnvelopeDestination,
+    "envelope_destination",
+    "Envelope destination",
+    &MOD_DESTINATIONS,
+  ),
+  enumeration(ParamId::Topology, "topology", "Topology", &TOPOLOGIES),
+  float(
+    ParamId::Diffusion,
+    "diffusion",
+    "Diffusion",
+    Unit::Percent,
+    (0., 100.),
+    0.,
+    1.,
+  ),
+  enumeration(
+    ParamId::SprayDistribution,
+    "spray_distribution",
+    "Spray distribution",
+    &SPRAY_DISTRIBUTIONS,
+  ),
+  enumeration(ParamId::PanMode, "pan_mode", "Pan mode", &PAN_MODES),
+  ParamDescriptor {
+    id: ParamId::InterpolationQuality,
+    symbol: "interpolation_quality",
+    name: "Interpolation",
+    unit: Unit::None,
+    kind: ParamKind::Enumeration(&INTERPOLATION_QUALITIES),
+    min: 0.,
+    max: 2.,
+    default: 1.,
+    skew: 1.,
+  },
+  enumeration(ParamId::TimeMode, "time_mode", "Time mode", &TIME_MODES),
+  float(
+    ParamId::TimeFade,
+    "time_fade",
+    "Time fade",
+    Unit::Milliseconds,
+    (10., 2000.),
+    200.,
+    3.,
+  ),
+];
+
+#[cfg(test)]
+mod tests {
+  use super::{ParamId, PARAMS};
+
+  #[test]
+  fn should_order_descriptors_by_id() {
+    PARAMS
+      .iter()
+      .enumerate()
+      .for_each(|(index, descriptor)| assert_eq!(descriptor.id as usize, index));
+  }
+
+  #[test]
+  fn should_map_values_to_and_from_the_normalized_range() {
+    let spray = ParamId::Spray.descriptor();
+    assert_eq!(spray.normalize(1.), 0.);
+    assert_eq!(spray.normalize(500.), 1.);
+    assert!((spray.denormalize(spray.normalize(100.)) - 100.).abs() < 1e-3);
+    let voices = ParamId::Voices.descriptor();
+    assert_eq!(voices.denormalize(0.5), 17.);
+    // Saved projects store normalized values, so the mapping of the time shouldn't change
+    assert!((ParamId::Time.descriptor().denormalize(0.5) - 625.875).abs() < 1e-2);
+  }
+
+  #[test]
+  fn should_format_values() {
+    assert_eq!(ParamId::Mix.descriptor().format(50.), "50.00 %");
+    assert_eq!(ParamId::Voices.descriptor().format(4.), "4");
+    assert_eq!(ParamId::Freeze.descriptor().format(1.), "On");
+    assert_eq!(ParamId::FeedbackMode.descriptor().format(2.), "Ping pong");
+    assert_eq!(ParamId::TimeDivision.descriptor().format(20.), "1/32");
+  }
+}
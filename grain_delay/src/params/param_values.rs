@@ -0,0 +1,361 @@
+use super::{BeatDivision, ParamId, PARAMS, PARAM_COUNT};
+use crate::{
+  feedback::FeedbackMode,
+  grains::{PanMode, SprayDistribution, WindowShape},
+  lfo::LfoShape,
+  modulation::ModDestination,
+  saturation::SaturationCurve,
+  shared::delay_line::InterpolationQuality,
+  state_variable_filter::FilterMode,
+  topology::Topology,
+  variable_delay_line::TimeMode,
+};
+use std::{error::Error, fmt};
+
+const DEFAULT_TEMPO: f32 = 120.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamError {
+  OutOfRange { id: ParamId, value: f32 },
+  InvalidTempo(f32),
+}
+
+impl fmt::Display for ParamError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::OutOfRange { id, value } => {
+        let descriptor = id.descriptor();
+        write!(
+          f,
+          "{} is {}, but should be between {} and {}",
+          descriptor.name, value, descriptor.min, descriptor.max
+        )
+      }
+      Self::InvalidTempo(tempo) => write!(f, "tempo is {}, but should be above zero", tempo),
+    }
+  }
+}
+
+impl Error for ParamError {}
+
+/// The parameter values in the units of the descriptor table, so percentages range from 0 to 100.
+/// Unset fields can be filled in with `..Default::default()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParamValues {
+  pub spray: f32,
+  pub frequency: f32,
+  pub pitch: f32,
+  pub drift: f32,
+  pub reverse: f32,
+  pub time: f32,
+  pub feedback: f32,
+  pub filter: f32,
+  pub spread: f32,
+  pub mix: f32,
+  pub voices: usize,
+  pub window_shape: WindowShape,
+  pub taper: f32,
+  pub feedback_mode: FeedbackMode,
+  pub cross_feedback: f32,
+  pub freeze: bool,
+  pub time_division: BeatDivision,
+  pub frequency_division: BeatDivision,
+  pub filter_mode: FilterMode,
+  pub resonance: f32,
+  pub saturation: SaturationCurve,
+  pub drive: f32,
+  pub duck_threshold: f32,
+  pub duck_amount: f32,
+  pub duck_attack: f32,
+  pub duck_release: f32,
+  pub lfo1_shape: LfoShape,
+  pub lfo1_rate: f32,
+  pub lfo1_sync: BeatDivision,
+  pub lfo1_depth: f32,
+  pub lfo1_destination: ModDestination,
+  pub lfo2_shape: LfoShape,
+  pub lfo2_rate: f32,
+  pub lfo2_sync: BeatDivision,
+  pub lfo2_depth: f32,
+  pub lfo2_destination: ModDestination,
+  pub envelope_attack: f32,
+  pub envelope_release: f32,
+  pub envelope_depth: f32,
+  pub envelope_destination: ModDestination,
+  pub topology: Topology,
+  pub diffusion: f32,
+  pub spray_distribution: SprayDistribution,
+  pub pan_mode: PanMode,
+  pub interpolation_quality: InterpolationQuality,
+  pub time_mode: TimeMode,
+  pub time_fade: f32,
+  pub tempo: f32,
+}
+
+impl Default for ParamValues {
+  fn default() -> Self {
+    let mut values = Self {
+      spray: 0.,
+      frequency: 0.,
+      pitch: 0.,
+      drift: 0.,
+      reverse: 0.,
+      time: 0.,
+      feedback: 0.,
+      filter: 0.,
+      spread: 0.,
+      mix: 0.,
+      voices: 0,
+      window_shape: WindowShape::Hann,
+      taper: 0.,
+      feedback_mode: FeedbackMode::Mono,
+      cross_feedback: 0.,
+      freeze: false,
+      time_division: BeatDivision::Free,
+      frequency_division: BeatDivision::Free,
+      filter_mode: FilterMode::LowPass,
+      resonance: 0.,
+      saturation: SaturationCurve::Clip,
+      drive: 0.,
+      duck_threshold: 0.,
+      duck_amount: 0.,
+      duck_attack: 0.,
+      duck_release: 0.,
+      lfo1_shape: LfoShape::Sine,
+      lfo1_rate: 0.,
+      lfo1_sync: BeatDivision::Free,
+      lfo1_depth: 0.,
+      lfo1_destination: ModDestination::Pitch,
+      lfo2_shape: LfoShape::Sine,
+      lfo2_rate: 0.,
+      lfo2_sync: BeatDivision::Free,
+      lfo2_depth: 0.,
+      lfo2_destination: ModDestination::Pitch,
+      envelope_attack: 0.,
+      envelope_release: 0.,
+      envelope_depth: 0.,
+      envelope_destination: ModDestination::Pitch,
+      topology: Topology::InLoop,
+      diffusion: 0.,
+      spray_distribution: SprayDistribution::Uniform,
+      pan_mode: PanMode::Random,
+      interpolation_quality: InterpolationQuality::Normal,
+      time_mode: TimeMode::Crossfade,
+      time_fade: 0.,
+      tempo: DEFAULT_TEMPO,
+    };
+    PARAMS
+      .iter()
+      .for_each(|descriptor| values.set_value(descriptor.id, descriptor.default));
+    values
+  }
+}
+
+impl ParamValues {
+  /// Creates the values from an array that is ordered by ParamId.
+  pub fn from_values(values: &[f32; PARAM_COUNT], tempo: f32) -> Self {
+    let mut param_values = Self {
+      tempo,
+      ..Default::default()
+    };
+    PARAMS
+      .iter()
+      .zip(values)
+      .for_each(|(descriptor, value)| param_values.set_value(descriptor.id, *value));
+    param_values
+  }
+
+  /// Returns the value of a parameter. Enumerations and toggles are returned as their index.
+  pub fn value(&self, id: ParamId) -> f32 {
+    match id {
+      ParamId::Spray => self.spray,
+      ParamId::Frequency => self.frequency,
+      ParamId::Pitch => self.pitch,
+      ParamId::Drift => self.drift,
+      ParamId::Reverse => self.reverse,
+      ParamId::Time => self.time,
+      ParamId::Feedback => self.feedback,
+      ParamId::Filter => self.filter,
+      ParamId::Spread => self.spread,
+      ParamId::Mix => self.mix,
+      ParamId::Voices => self.voices as f32,
+      ParamId::WindowShape => self.window_shape as i32 as f32,
+      ParamId::Taper => self.taper,
+      ParamId::FeedbackMode => self.feedback_mode as i32 as f32,
+      ParamId::CrossFeedback => self.cross_feedback,
+      ParamId::Freeze => self.freeze as i32 as f32,
+      ParamId::TimeDivision => self.time_division as i32 as f32,
+      ParamId::FrequencyDivision => self.frequency_division as i32 as f32,
+      ParamId::FilterMode => self.filter_mode as i32 as f32,
+      ParamId::Resonance => self.resonance,
+      ParamId::Saturation => self.saturation as i32 as f32,
+      ParamId::Drive => self.drive,
+      ParamId::DuckThreshold => self.duck_threshold,
+      ParamId::DuckAmount => self.duck_amount,
+      ParamId::DuckAttack => self.duck_attack,
+      ParamId::DuckRelease => self.duck_release,
+      ParamId::Lfo1Shape => self.lfo1_shape as i32 as f32,
+      ParamId::Lfo1Rate => self.lfo1_rate,
+      ParamId::Lfo1Sync => self.lfo1_sync as i32 as f32,
+      ParamId::Lfo1Depth => self.lfo1_depth,
+      ParamId::Lfo1Destination => self.lfo1_destination as i32 as f32,
+      ParamId::Lfo2Shape => self.lfo2_shape as i32 as f32,
+      ParamId::Lfo2Rate => self.lfo2_rate,
+      ParamId::Lfo2Sync => self.lfo2_sync as i32 as f32,
+      ParamId::Lfo2Depth => self.lfo2_depth,
+      ParamId::Lfo2Destination => self.lfo2_destination as i32 as f32,
+      ParamId::EnvelopeAttack => self.envelope_attack,
+      ParamId::EnvelopeRelease => self.envelope_release,
+      ParamId::EnvelopeDepth => self.envelope_depth,
+      ParamId::EnvelopeDestination => self.envelope_destination as i32 as f32,
+      ParamId::Topology => self.topology as i32 as f32,
+      ParamId::Diffusion => self.diffusion,
+      ParamId::SprayDistribution => self.spray_distribution as i32 as f32,
+      ParamId::PanMode => self.pan_mode as i32 as f32,
+      ParamId::InterpolationQuality => self.interpolation_quality as i32 as f32,
+      ParamId::TimeMode => self.time_mode as i32 as f32,
+      ParamId::TimeFade => self.time_fade,
+    }
+  }
+
+  pub fn set_value(&mut self, id: ParamId, value: f32) {
+    match id {
+      ParamId::Spray => self.spray = value,
+      ParamId::Frequency => self.frequency = value,
+      ParamId::Pitch => self.pitch = value,
+      ParamId::Drift => self.drift = value,
+      ParamId::Reverse => self.reverse = value,
+      ParamId::Time => self.time = value,
+      ParamId::Feedback => self.feedback = value,
+      ParamId::Filter => self.filter = value,
+      ParamId::Spread => self.spread = value,
+      ParamId::Mix => self.mix = value,
+      ParamId::Voices => self.voices = value as usize,
+      ParamId::WindowShape => self.window_shape = WindowShape::from(value as i32),
+      ParamId::Taper => self.taper = value,
+      ParamId::FeedbackMode => self.feedback_mode = FeedbackMode::from(value as i32),
+      ParamId::CrossFeedback => self.cross_feedback = value,
+      ParamId::Freeze => self.freeze = value == 1.,
+      ParamId::TimeDivision => self.time_division = BeatDivision::from(value as i32),
+      ParamId::FrequencyDivision => self.frequency_division = BeatDivision::from(value as i32),
+      ParamId::FilterMode => self.filter_mode = FilterMode::from(value as i32),
+      ParamId::Resonance => self.resonance = value,
+      ParamId::Saturation => self.saturation = SaturationCurve::from(value as i32),
+      ParamId::Drive => self.drive = value,
+      ParamId::DuckThreshold => self.duck_threshold = value,
+      ParamId::DuckAmount => self.duck_amount = value,
+      ParamId::DuckAttack => self.duck_attack = value,
+      ParamId::DuckRelease => self.duck_release = value,
+      ParamId::Lfo1Shape => self.lfo1_shape = LfoShape::from(value as i32),
+      ParamId::Lfo1Rate => self.lfo1_rate = value,
+      ParamId::Lfo1Sync => self.lfo1_sync = BeatDivision::from(value as i32),
+      ParamId::Lfo1Depth => self.lfo1_depth = value,
+      ParamId::Lfo1Destination => self.lfo1_destination = ModDestination::from(value as i32),
+      ParamId::Lfo2Shape => self.lfo2_shape = LfoShape::from(value as i32),
+      ParamId::Lfo2Rate => self.lfo2_rate = value,
+      ParamId::Lfo2Sync => self.lfo2_sync = BeatDivision::from(value as i32),
+      ParamId::Lfo2Depth => self.lfo2_depth = value,
+      ParamId::Lfo2Destination => self.lfo2_destination = ModDestination::from(value as i32),
+      ParamId::EnvelopeAttack => self.envelope_attack = value,
+      ParamId::EnvelopeRelease => self.envelope_release = value,
+      ParamId::EnvelopeDepth => self.envelope_depth = value,
+      ParamId::EnvelopeDestination => {
+        self.envelope_destination = ModDestination::from(value as i32)
+      }
+      ParamId::Topology => self.topology = Topology::from(value as i32),
+      ParamId::Diffusion => self.diffusion = value,
+      ParamId::SprayDistribution => self.spray_distribution = SprayDistribution::from(value as i32),
+      ParamId::PanMode => self.pan_mode = PanMode::from(value as i32),
+      ParamId::InterpolationQuality => {
+        self.interpolation_quality = InterpolationQuality::from(value as i32)
+      }
+      ParamId::TimeMode => self.time_mode = TimeMode::from(value as i32),
+      ParamId::TimeFade => self.time_fade = value,
+    }
+  }
+
+  /// Returns an error for the first value that is outside of its range.
+  pub fn validate(&self) -> Result<(), ParamError> {
+    if !(self.tempo > 0. && self.tempo.is_finite()) {
+      return Err(ParamError::InvalidTempo(self.tempo));
+    }
+    PARAMS.iter().try_for_each(|descriptor| {
+      let value = self.value(descriptor.id);
+      if value >= descriptor.min && value <= descriptor.max {
+        Ok(())
+      } else {
+        Err(ParamError::OutOfRange {
+          id: descriptor.id,
+          value,
+        })
+      }
+    })
+  }
+
+  /// Clamps the values to their range. Values that aren't a number are replaced by their default.
+  pub fn clamped(mut self) -> Self {
+    PARAMS.iter().for_each(|descriptor| {
+      let value = self.value(descriptor.id);
+      let value = if value.is_nan() {
+        descriptor.default
+      } else {
+        value.clamp(descriptor.min, descriptor.max)
+      };
+      self.set_value(descriptor.id, value);
+    });
+    if !(self.tempo > 0. && self.tempo.is_finite()) {
+      self.tempo = DEFAULT_TEMPO;
+    }
+    self
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ParamError, ParamValues};
+  use crate::{ParamId, PARAMS};
+
+  #[test]
+  fn should_default_to_the_descriptor_defaults() {
+    let values = ParamValues::default();
+    PARAMS
+      .iter()
+      .for_each(|descriptor| assert_eq!(values.value(descriptor.id), descriptor.default));
+    assert_eq!(values.validate(), Ok(()));
+  }
+
+  #[test]
+  fn should_reject_out_of_range_values() {
+    let values = ParamValues {
+      frequency: 0.,
+      ..Default::default()
+    };
+    assert_eq!(
+      values.validate(),
+      Err(ParamError::OutOfRange {
+        id: ParamId::Frequency,
+        value: 0.
+      })
+    );
+    let values = ParamValues {
+      tempo: 0.,
+      ..Default::default()
+    };
+    assert_eq!(values.validate(), Err(ParamError::InvalidTempo(0.)));
+  }
+
+  #[test]
+  fn should_clamp_out_of_range_values() {
+    let values = ParamValues {
+      frequency: 0.,
+      mix: f32::NAN,
+      voices: 64,
+      ..Default::default()
+    }
+    .clamped();
+    assert_eq!(values.frequency, 1.);
+    assert_eq!(values.mix, 50.);
+    assert_eq!(values.voices, 32);
+    assert_eq!(values.validate(), Ok(()));
+  }
+}
@@ -1,6 +1,8 @@
 use std::f32::consts::TAU;
 
 pub trait Smoother {
+  /// Jumps to the target and stays there. The target is set as well, because `next` moves towards
+  /// it and would otherwise fall back to the target from before the reset.
   fn reset(&mut self, target: f32);
 
   fn set_target(&mut self, target: f32);
@@ -35,6 +37,7 @@ impl LinearSmooth {
 impl Smoother for LinearSmooth {
   fn reset(&mut self, target: f32) {
     self.current = target;
+    self.target = target;
     self.step_counter = 0;
   }
 
@@ -82,6 +85,7 @@ impl ExponentialSmooth {
 impl Smoother for ExponentialSmooth {
   fn reset(&mut self, target: f32) {
     self.current = target;
+    self.target = target;
     self.is_active = false;
   }
 
@@ -128,6 +132,7 @@ impl LogarithmicSmooth {
 impl Smoother for LogarithmicSmooth {
   fn reset(&mut self, target: f32) {
     self.current = target;
+    self.target = target;
     self.is_active = false;
   }
 
@@ -178,4 +183,12 @@ mod tests {
     assert_eq!(linear_smooth.next(), 2.9802322e-8);
     assert_eq!(linear_smooth.next(), 0.0);
   }
+
+  #[test]
+  fn should_hold_the_value_after_a_reset() {
+    let mut linear_smooth = LinearSmooth::new(5., 1.);
+    linear_smooth.reset(0.5);
+    assert_eq!(linear_smooth.next(), 0.5);
+    assert_eq!(linear_smooth.get_target(), 0.5);
+  }
 }
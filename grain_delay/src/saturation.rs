@@ -0,0 +1,133 @@
+use crate::shared::float_ext::FloatExt;
+use std::f32::consts::{FRAC_2_PI, FRAC_PI_2};
+
+/// The gain at full drive.
+pub const MAX_DRIVE: f32 = 4.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaturationCurve {
+  Clip,
+  Tanh,
+  FastTanh,
+  RationalTanh,
+  Atan,
+}
+
+impl From<i32> for SaturationCurve {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::Tanh,
+      2 => Self::FastTanh,
+      3 => Self::RationalTanh,
+      4 => Self::Atan,
+      _ => Self::Clip,
+    }
+  }
+}
+
+impl SaturationCurve {
+  /// Each curve has a slope of one around zero and never exceeds one.
+  fn apply(&self, input: f32) -> f32 {
+    match self {
+      Self::Clip => input.clamp(-1., 1.),
+      // The approximations are only accurate within a limited range
+      Self::Tanh => input.clamp(-3., 3.).fast_tanh1(),
+      Self::FastTanh => input.clamp(-2.5, 2.5).fast_tanh2(),
+      Self::RationalTanh => (input / 1.2617567).fast_tanh3(),
+      Self::Atan => {
+        let x = input * FRAC_PI_2;
+        let atan = if x.abs() <= 1. {
+          x.fast_atan1()
+        } else {
+          FRAC_PI_2.copysign(x) - x.recip().fast_atan1()
+        };
+        atan * FRAC_2_PI
+      }
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
+pub struct Saturation {
+  pub curve: SaturationCurve,
+  /// Lowers the knee to the reciprocal of the drive. Below the knee the signal passes unchanged and
+  /// above it the curve bends the rest of the range towards one, so the drive adds saturation
+  /// without changing the loop gain of small signals or the ceiling.
+  pub drive: f32,
+}
+
+impl Saturation {
+  pub fn process(&self, input: f32) -> f32 {
+    let knee = self.drive.recip();
+    let magnitude = input.abs();
+    if magnitude <= knee {
+      input
+    } else if knee >= 1. {
+      input.signum()
+    } else {
+      let range = 1. - knee;
+      (knee + range * self.curve.apply((magnitude - knee) / range)).copysign(input)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Saturation, SaturationCurve};
+
+  const CURVES: [SaturationCurve; 5] = [
+    SaturationCurve::Clip,
+    SaturationCurve::Tanh,
+    SaturationCurve::FastTanh,
+    SaturationCurve::RationalTanh,
+    SaturationCurve::Atan,
+  ];
+
+  #[test]
+  fn should_pass_small_signals_and_bound_large_signals() {
+    for curve in CURVES {
+      let saturation = Saturation { curve, drive: 1. };
+      assert!((saturation.process(0.01) - 0.01).abs() < 1e-3);
+      assert!((saturation.process(-0.01) + 0.01).abs() < 1e-3);
+      let mut previous = 0.;
+      for i in 1..=100 {
+        let output = saturation.process(i as f32 * 0.1);
+        assert!(output <= 1.);
+        assert!(output >= previous - 0.01);
+        previous = output;
+      }
+    }
+  }
+
+  #[test]
+  fn should_keep_the_ceiling_with_drive() {
+    for curve in CURVES {
+      let saturation = Saturation { curve, drive: 4. };
+      let output = saturation.process(100.);
+      assert!(output > 0.98 && output <= 1.);
+    }
+    let saturation = Saturation {
+      curve: SaturationCurve::Clip,
+      drive: 4.,
+    };
+    assert_eq!(saturation.process(1.), 1.);
+    assert_eq!(saturation.process(-2.), -1.);
+  }
+
+  #[test]
+  fn should_keep_the_small_signal_gain_with_drive() {
+    for curve in CURVES {
+      for drive in [1., 2., 4.] {
+        let saturation = Saturation { curve, drive };
+        assert_eq!(saturation.process(0.2), 0.2);
+        assert_eq!(saturation.process(-0.2), -0.2);
+      }
+      let saturation = Saturation { curve, drive: 4. };
+      // Just above the knee the curve continues with the same slope
+      assert!((saturation.process(0.26) - 0.26).abs() < 1e-3);
+      if curve != SaturationCurve::Clip {
+        assert!(saturation.process(0.6) < 0.59);
+      }
+    }
+  }
+}
@@ -1,13 +1,58 @@
-use std::f32::consts::PI;
+use std::{f32::consts::PI, sync::OnceLock};
 
-#[allow(dead_code)]
-#[derive(Clone, Copy)]
+const SINC_TAPS: usize = 8;
+const SINC_PHASES: usize = 512;
+const SINC_CUTOFF: f32 = 0.9;
+// Each table halves the cutoff and doubles the taps, which band limits reads up to four times the
+// original speed.
+const SINC_TABLES: usize = 3;
+const LAGRANGE_POINTS: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Interpolation {
   Step,
   Linear,
   Cosine,
   Cubic,
   Spline,
+  Lagrange,
+  Sinc,
+}
+
+/// Picks the interpolation of each read site, trading processing power for quality.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InterpolationQuality {
+  Eco,
+  Normal,
+  High,
+}
+
+impl From<i32> for InterpolationQuality {
+  fn from(value: i32) -> Self {
+    match value {
+      0 => Self::Eco,
+      2 => Self::High,
+      _ => Self::Normal,
+    }
+  }
+}
+
+impl InterpolationQuality {
+  pub fn delay(&self) -> Interpolation {
+    match self {
+      Self::Eco => Interpolation::Step,
+      Self::Normal => Interpolation::Cubic,
+      Self::High => Interpolation::Lagrange,
+    }
+  }
+
+  pub fn grains(&self) -> Interpolation {
+    match self {
+      Self::Eco => Interpolation::Cosine,
+      Self::Normal => Interpolation::Spline,
+      Self::High => Interpolation::Sinc,
+    }
+  }
 }
 
 #[derive(Clone)]
@@ -21,6 +66,8 @@ pub struct DelayLine {
 impl DelayLine {
   pub fn new(length: usize, sample_rate: f32) -> Self {
     let size = length.next_power_of_two();
+    // Builds the sinc tables up front, rather than on the audio thread on the first sinc read
+    sinc_tables();
     Self {
       buffer: vec![0.0; size],
       write_pointer: 0,
@@ -29,6 +76,21 @@ impl DelayLine {
     }
   }
 
+  /// Changes the sample rate that times are converted with. The length of the buffer stays the same.
+  pub fn set_sample_rate(&mut self, sample_rate: f32) {
+    self.sample_rate = sample_rate;
+  }
+
+  /// Returns the length of the buffer, which is the requested length rounded up to a power of two.
+  pub fn len(&self) -> usize {
+    self.buffer.len()
+  }
+
+  pub fn reset(&mut self) {
+    self.buffer.fill(0.);
+    self.write_pointer = 0;
+  }
+
   pub fn read(&self, time: f32, interp: Interpolation) -> f32 {
     match interp {
       Interpolation::Step => self.step_interp(time),
@@ -36,6 +98,17 @@ impl DelayLine {
       Interpolation::Cosine => self.cosine_interp(time),
       Interpolation::Cubic => self.cubic_interp(time),
       Interpolation::Spline => self.spline_interp(time),
+      Interpolation::Lagrange => self.lagrange_interp(time),
+      Interpolation::Sinc => self.sinc_interp(time, 1.),
+    }
+  }
+
+  /// Reads with the playback speed of the read position, so the sinc interpolator can lower its
+  /// cutoff when the read position moves faster than the write position.
+  pub fn read_at_speed(&self, time: f32, speed: f32, interp: Interpolation) -> f32 {
+    match interp {
+      Interpolation::Sinc => self.sinc_interp(time, speed.abs()),
+      _ => self.read(time, interp),
     }
   }
 
@@ -73,7 +146,7 @@ impl DelayLine {
 
     let cosine_mix = (1. - (mix * PI).cos()) / 2.;
     let x = self.buffer[index & self.wrap];
-    let y = self.buffer[index + 1 & self.wrap];
+    let y = self.buffer[(index + 1) & self.wrap];
     x + (y - x) * cosine_mix
   }
 
@@ -84,20 +157,20 @@ impl DelayLine {
     let mix = read_pointer - rounded_read_pointer;
     let index = rounded_read_pointer as usize;
 
-    let w = self.buffer[index & self.wrap];
-    let x = self.buffer[index + 1 & self.wrap];
-    let y = self.buffer[index + 2 & self.wrap];
-    let z = self.buffer[index + 3 & self.wrap];
+    let w = self.buffer[(index + self.buffer.len() - 1) & self.wrap];
+    let x = self.buffer[index & self.wrap];
+    let y = self.buffer[(index + 1) & self.wrap];
+    let z = self.buffer[(index + 2) & self.wrap];
 
     let a1 = 1. + mix;
     let aa = mix * a1;
     let b = 1. - mix;
     let b1 = 2. - mix;
     let bb = b * b1;
-    let fw = -0.1666667 * bb * mix;
+    let fw = -bb * mix / 6.;
     let fx = 0.5 * bb * a1;
     let fy = 0.5 * aa * b1;
-    let fz = -0.1666667 * aa * b;
+    let fz = -aa * b / 6.;
     w * fw + x * fx + y * fy + z * fz
   }
 
@@ -108,19 +181,194 @@ impl DelayLine {
     let mix = read_pointer - rounded_read_pointer;
     let index = rounded_read_pointer as usize;
 
-    let w = self.buffer[index & self.wrap];
-    let x = self.buffer[index + 1 & self.wrap];
-    let y = self.buffer[index + 2 & self.wrap];
-    let z = self.buffer[index + 3 & self.wrap];
+    let w = self.buffer[(index + self.buffer.len() - 1) & self.wrap];
+    let x = self.buffer[index & self.wrap];
+    let y = self.buffer[(index + 1) & self.wrap];
+    let z = self.buffer[(index + 2) & self.wrap];
 
     let c0 = x;
-    let c1 = (0.5) * (y - w);
-    let c2 = w - (2.5) * x + y + y - (0.5) * z;
-    let c3 = (0.5) * (z - w) + (1.5) * (x - y);
+    let c1 = 0.5 * (y - w);
+    let c2 = w - 2.5 * x + y + y - 0.5 * z;
+    let c3 = 0.5 * (z - w) + 1.5 * (x - y);
     ((c3 * mix + c2) * mix + c1) * mix + c0
   }
 
+  /// A fifth order Lagrange interpolator over six samples, which keeps more of the high end than
+  /// the cubic interpolator.
+  fn lagrange_interp(&self, time: f32) -> f32 {
+    let half_points = LAGRANGE_POINTS / 2;
+    let read_pointer = (self.write_pointer + self.buffer.len()) as f32
+      - self.mstosamps(time).max(half_points as f32);
+    let rounded_read_pointer = read_pointer.trunc();
+    let mix = read_pointer - rounded_read_pointer;
+    let index = rounded_read_pointer as usize + self.buffer.len() + 1 - half_points;
+
+    // The samples sit at the offsets -2 to 3 from the read position
+    (0..LAGRANGE_POINTS)
+      .map(|i| {
+        let offset = i as f32 + 1. - half_points as f32;
+        let weight = (0..LAGRANGE_POINTS)
+          .filter(|j| *j != i)
+          .map(|j| {
+            let other = j as f32 + 1. - half_points as f32;
+            (mix - other) / (offset - other)
+          })
+          .product::<f32>();
+        self.buffer[(index + i) & self.wrap] * weight
+      })
+      .sum()
+  }
+
+  /// A windowed sinc interpolator, which is band limited to the playback speed to avoid aliasing.
+  /// Speeds between two octaves blend the tables of both octaves, so the cutoff follows the speed
+  /// without jumps.
+  fn sinc_interp(&self, time: f32, speed: f32) -> f32 {
+    let tables = sinc_tables();
+    let octave = speed.max(1.).log2().min((SINC_TABLES - 1) as f32);
+    let table = octave as usize;
+    let mix = octave - table as f32;
+    let lower = self.sinc_read(time, &tables[table]);
+    if mix > 0. {
+      lower + (self.sinc_read(time, &tables[table + 1]) - lower) * mix
+    } else {
+      lower
+    }
+  }
+
+  fn sinc_read(&self, time: f32, table: &SincTable) -> f32 {
+    let half_taps = table.taps / 2;
+    let read_pointer =
+      (self.write_pointer + self.buffer.len()) as f32 - self.mstosamps(time).max(half_taps as f32);
+    let rounded_read_pointer = read_pointer.trunc();
+    let mix = read_pointer - rounded_read_pointer;
+    let index = rounded_read_pointer as usize + self.buffer.len() + 1 - half_taps;
+
+    let phase = (mix * SINC_PHASES as f32).round() as usize;
+    table.coefficients[phase * table.taps..(phase + 1) * table.taps]
+      .iter()
+      .enumerate()
+      .map(|(i, tap)| self.buffer[(index + i) & self.wrap] * tap)
+      .sum()
+  }
+
   fn mstosamps(&self, time: f32) -> f32 {
     time * 0.001 * self.sample_rate
   }
 }
+
+struct SincTable {
+  taps: usize,
+  /// The taps of each fractional position after one another.
+  coefficients: Vec<f32>,
+}
+
+impl SincTable {
+  /// The Blackman windowed sinc taps for each fractional position, which are normalized to unity
+  /// gain.
+  fn new(taps: usize, cutoff: f32) -> Self {
+    let half_taps = (taps / 2) as f32;
+    let coefficients = (0..=SINC_PHASES)
+      .flat_map(|phase| {
+        let mix = phase as f32 / SINC_PHASES as f32;
+        let kernel: Vec<f32> = (0..taps)
+          .map(|i| {
+            let x = i as f32 + 1. - half_taps - mix;
+            let sinc = if x == 0. {
+              1.
+            } else {
+              (PI * x * cutoff).sin() / (PI * x * cutoff)
+            };
+            let window =
+              0.42 + 0.5 * (PI * x / half_taps).cos() + 0.08 * (2. * PI * x / half_taps).cos();
+            sinc * window
+          })
+          .collect();
+        let sum: f32 = kernel.iter().sum();
+        kernel.into_iter().map(move |tap| tap / sum)
+      })
+      .collect();
+    Self { taps, coefficients }
+  }
+}
+
+fn sinc_tables() -> &'static [SincTable] {
+  static SINC_TABLES_CELL: OnceLock<Vec<SincTable>> = OnceLock::new();
+  SINC_TABLES_CELL.get_or_init(|| {
+    (0..SINC_TABLES)
+      .map(|octave| SincTable::new(SINC_TAPS << octave, SINC_CUTOFF / (1 << octave) as f32))
+      .collect()
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{DelayLine, Interpolation};
+
+  const INTERPOLATORS: [Interpolation; 6] = [
+    Interpolation::Linear,
+    Interpolation::Cosine,
+    Interpolation::Cubic,
+    Interpolation::Spline,
+    Interpolation::Lagrange,
+    Interpolation::Sinc,
+  ];
+
+  fn get_delay_line() -> DelayLine {
+    // A sample rate of 1000 makes one millisecond a single sample
+    let mut delay_line = DelayLine::new(64, 1000.);
+    (0..64).for_each(|i| delay_line.write((i as f32 * 0.1).sin()));
+    delay_line
+  }
+
+  #[test]
+  fn should_read_integer_delays_exactly() {
+    let delay_line = get_delay_line();
+    for interp in INTERPOLATORS {
+      assert!((delay_line.read(5., interp) - (59. * 0.1_f32).sin()).abs() < 1e-5);
+      assert!((delay_line.read(10., interp) - (54. * 0.1_f32).sin()).abs() < 1e-5);
+    }
+  }
+
+  #[test]
+  fn should_read_the_full_buffer_length() {
+    let delay_line = get_delay_line();
+    for interp in INTERPOLATORS {
+      assert!(delay_line.read(64., interp).is_finite());
+    }
+  }
+
+  #[test]
+  fn should_filter_high_frequencies_when_reading_faster() {
+    let mut delay_line = DelayLine::new(64, 1000.);
+    // A signal at a quarter of the sample rate, which aliases when it's read at twice the speed
+    (0..64).for_each(|i| delay_line.write([1., 0., -1., 0.][i % 4]));
+    let peak = |speed: f32| {
+      (0..8)
+        .map(|i| {
+          delay_line
+            .read_at_speed(20. + i as f32 * 0.5, speed, Interpolation::Sinc)
+            .abs()
+        })
+        .fold(0., f32::max)
+    };
+    assert!(peak(1.) > 0.5);
+    assert!(peak(4.) < 0.1);
+    // Slightly faster reads should only filter slightly more
+    assert!(peak(1.) - peak(1.05) < 0.05);
+    assert!(peak(1.95) - peak(2.) < 0.05);
+  }
+
+  #[test]
+  fn should_interpolate_fractional_delays() {
+    let delay_line = get_delay_line();
+    let expected = (58.5 * 0.1_f32).sin();
+    for interp in [
+      Interpolation::Cubic,
+      Interpolation::Spline,
+      Interpolation::Lagrange,
+    ] {
+      assert!((delay_line.read(5.5, interp) - expected).abs() < 1e-3);
+    }
+    assert!((delay_line.read(5.5, Interpolation::Sinc) - expected).abs() < 1e-2);
+  }
+}
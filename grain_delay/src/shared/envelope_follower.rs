@@ -0,0 +1,66 @@
+/// Follows the peak level of a signal with separate attack and release times in milliseconds.
+pub struct EnvelopeFollower {
+  samples_per_ms: f32,
+  envelope: f32,
+  attack: f32,
+  release: f32,
+  attack_coeff: f32,
+  release_coeff: f32,
+}
+
+impl EnvelopeFollower {
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      samples_per_ms: sample_rate * 0.001,
+      envelope: 0.,
+      attack: 0.,
+      release: 0.,
+      attack_coeff: 0.,
+      release_coeff: 0.,
+    }
+  }
+
+  pub fn process(&mut self, input: f32, attack: f32, release: f32) -> f32 {
+    if attack != self.attack {
+      self.attack = attack;
+      self.attack_coeff = self.get_coeff(attack);
+    }
+    if release != self.release {
+      self.release = release;
+      self.release_coeff = self.get_coeff(release);
+    }
+
+    let input = input.abs();
+    let coeff = if input > self.envelope {
+      self.attack_coeff
+    } else {
+      self.release_coeff
+    };
+    self.envelope = input + (self.envelope - input) * coeff;
+    self.envelope
+  }
+
+  fn get_coeff(&self, time: f32) -> f32 {
+    (-(time * self.samples_per_ms).max(1.).recip()).exp()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::EnvelopeFollower;
+
+  #[test]
+  fn should_attack_fast_and_release_slowly() {
+    let mut envelope_follower = EnvelopeFollower::new(1000.);
+    let attacked = (0..10)
+      .map(|_| envelope_follower.process(-1., 1., 100.))
+      .last()
+      .unwrap();
+    assert!(attacked > 0.99);
+    let released = (0..10)
+      .map(|_| envelope_follower.process(0., 1., 100.))
+      .last()
+      .unwrap();
+    assert!(released > 0.9 && released < attacked);
+  }
+}
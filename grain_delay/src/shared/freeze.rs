@@ -0,0 +1,51 @@
+const FADE_TIME: f32 = 0.01;
+
+/// Fades the recording of a buffer in and out, so freezing and unfreezing doesn't cause clicks.
+pub struct Freeze {
+  gain: f32,
+  step_size: f32,
+}
+
+impl Freeze {
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      gain: 1.,
+      step_size: (sample_rate * FADE_TIME).recip(),
+    }
+  }
+
+  /// Returns the gain to record with, or None when recording has been halted.
+  pub fn process(&mut self, freeze: bool) -> Option<f32> {
+    if freeze {
+      if self.gain == 0. {
+        return None;
+      }
+      self.gain = (self.gain - self.step_size).max(0.);
+    } else if self.gain < 1. {
+      self.gain = (self.gain + self.step_size).min(1.);
+    }
+    Some(self.gain)
+  }
+
+  pub fn is_frozen(&self) -> bool {
+    self.gain == 0.
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Freeze;
+
+  #[test]
+  fn should_fade_out_before_halting() {
+    let mut freeze = Freeze::new(200.);
+    assert_eq!(freeze.process(false), Some(1.));
+    assert_eq!(freeze.process(true), Some(0.5));
+    assert_eq!(freeze.process(true), Some(0.));
+    assert!(freeze.is_frozen());
+    assert_eq!(freeze.process(true), None);
+    assert_eq!(freeze.process(false), Some(0.5));
+    assert!(!freeze.is_frozen());
+    assert_eq!(freeze.process(false), Some(1.));
+  }
+}
@@ -0,0 +1,97 @@
+use super::delay_line::{DelayLine, Interpolation};
+
+/// A delay line for each channel. While both channels hold the same signal, as they do for a mono
+/// input, only the left channel is read.
+#[derive(Clone)]
+pub struct StereoDelayLine {
+  left: DelayLine,
+  right: DelayLine,
+  // The number of samples that were written with the same value to both channels in a row
+  linked_samples: usize,
+}
+
+impl StereoDelayLine {
+  pub fn new(length: usize, sample_rate: f32) -> Self {
+    let delay_line = DelayLine::new(length, sample_rate);
+    Self {
+      left: delay_line.clone(),
+      right: delay_line,
+      linked_samples: usize::MAX,
+    }
+  }
+
+  /// Changes the sample rate that times are converted with. The length of the buffers stays the same.
+  pub fn set_sample_rate(&mut self, sample_rate: f32) {
+    self.left.set_sample_rate(sample_rate);
+    self.right.set_sample_rate(sample_rate);
+  }
+
+  pub fn reset(&mut self) {
+    self.left.reset();
+    self.right.reset();
+    self.linked_samples = usize::MAX;
+  }
+
+  pub fn read(&self, time: f32, interp: Interpolation) -> (f32, f32) {
+    let left = self.left.read(time, interp);
+    if self.is_linked() {
+      (left, left)
+    } else {
+      (left, self.right.read(time, interp))
+    }
+  }
+
+  pub fn read_at_speed(&self, time: f32, speed: f32, interp: Interpolation) -> (f32, f32) {
+    let left = self.left.read_at_speed(time, speed, interp);
+    if self.is_linked() {
+      (left, left)
+    } else {
+      (left, self.right.read_at_speed(time, speed, interp))
+    }
+  }
+
+  pub fn write(&mut self, value: (f32, f32)) {
+    self.left.write(value.0);
+    self.right.write(value.1);
+    self.linked_samples = if value.0 == value.1 {
+      self.linked_samples.saturating_add(1)
+    } else {
+      0
+    };
+  }
+
+  // Once a whole buffer has been written with the same values, both channels read the same.
+  fn is_linked(&self) -> bool {
+    self.linked_samples >= self.left.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::StereoDelayLine;
+  use crate::shared::delay_line::Interpolation;
+
+  #[test]
+  fn should_read_both_channels_until_they_are_linked_again() {
+    let mut delay_line = StereoDelayLine::new(4, 1000.);
+    delay_line.write((1., 0.));
+    assert_eq!(delay_line.read(1., Interpolation::Step), (1., 0.));
+    for _ in 0..3 {
+      delay_line.write((0.5, 0.5));
+      assert!(!delay_line.is_linked());
+    }
+    assert_eq!(delay_line.read(4., Interpolation::Step), (1., 0.));
+    delay_line.write((0.5, 0.5));
+    assert!(delay_line.is_linked());
+    assert_eq!(delay_line.read(4., Interpolation::Step), (0.5, 0.5));
+  }
+
+  #[test]
+  fn should_be_linked_after_a_reset() {
+    let mut delay_line = StereoDelayLine::new(4, 1000.);
+    delay_line.write((1., 0.));
+    delay_line.reset();
+    assert!(delay_line.is_linked());
+    assert_eq!(delay_line.read(1., Interpolation::Linear), (0., 0.));
+  }
+}
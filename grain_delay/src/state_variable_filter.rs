@@ -0,0 +1,143 @@
+use std::f32::consts::PI;
+
+const MAX_RESONANCE: f32 = 0.98;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterMode {
+  LowPass,
+  HighPass,
+  BandPass,
+  Notch,
+}
+
+impl From<i32> for FilterMode {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::HighPass,
+      2 => Self::BandPass,
+      3 => Self::Notch,
+      _ => Self::LowPass,
+    }
+  }
+}
+
+/// A trapezoidal integrated state variable filter, which stays stable while the cutoff is modulated.
+pub struct StateVariableFilter {
+  pi_over_sample_rate: f32,
+  max_freq: f32,
+  ic1eq: (f32, f32),
+  ic2eq: (f32, f32),
+  prev_freq: f32,
+  prev_resonance: f32,
+  k: f32,
+  a1: f32,
+  a2: f32,
+  a3: f32,
+}
+
+impl StateVariableFilter {
+  pub fn new(sample_rate: f32) -> Self {
+    Self {
+      pi_over_sample_rate: PI / sample_rate,
+      max_freq: sample_rate * 0.49,
+      ic1eq: (0., 0.),
+      ic2eq: (0., 0.),
+      prev_freq: 0.,
+      prev_resonance: -1.,
+      k: 0.,
+      a1: 0.,
+      a2: 0.,
+      a3: 0.,
+    }
+  }
+
+  /// The resonance ranges from 0 to 1, where 0 doesn't add a resonant peak.
+  pub fn process(
+    &mut self,
+    input: (f32, f32),
+    freq: f32,
+    resonance: f32,
+    mode: FilterMode,
+  ) -> (f32, f32) {
+    if freq != self.prev_freq || resonance != self.prev_resonance {
+      self.set_coefficients(freq, resonance);
+    }
+
+    (
+      Self::process_channel(
+        input.0,
+        &mut self.ic1eq.0,
+        &mut self.ic2eq.0,
+        (self.k, self.a1, self.a2, self.a3),
+        mode,
+      ),
+      Self::process_channel(
+        input.1,
+        &mut self.ic1eq.1,
+        &mut self.ic2eq.1,
+        (self.k, self.a1, self.a2, self.a3),
+        mode,
+      ),
+    )
+  }
+
+  fn set_coefficients(&mut self, freq: f32, resonance: f32) {
+    let g = (freq.min(self.max_freq) * self.pi_over_sample_rate).tan();
+    self.k = 2. - 2. * resonance.clamp(0., 1.) * MAX_RESONANCE;
+    self.a1 = (1. + g * (g + self.k)).recip();
+    self.a2 = g * self.a1;
+    self.a3 = g * self.a2;
+    self.prev_freq = freq;
+    self.prev_resonance = resonance;
+  }
+
+  fn process_channel(
+    input: f32,
+    ic1eq: &mut f32,
+    ic2eq: &mut f32,
+    (k, a1, a2, a3): (f32, f32, f32, f32),
+    mode: FilterMode,
+  ) -> f32 {
+    let v3 = input - *ic2eq;
+    let v1 = a1 * *ic1eq + a2 * v3;
+    let v2 = *ic2eq + a2 * *ic1eq + a3 * v3;
+    *ic1eq = 2. * v1 - *ic1eq;
+    *ic2eq = 2. * v2 - *ic2eq;
+
+    match mode {
+      FilterMode::LowPass => v2,
+      FilterMode::HighPass => input - k * v1 - v2,
+      FilterMode::BandPass => k * v1,
+      FilterMode::Notch => input - k * v1,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{FilterMode, StateVariableFilter};
+
+  fn get_gain(mode: FilterMode, signal_freq: f32) -> f32 {
+    let sample_rate = 44100.;
+    let mut filter = StateVariableFilter::new(sample_rate);
+    (0..4410)
+      .map(|i| {
+        let input = (i as f32 * signal_freq / sample_rate * std::f32::consts::TAU).sin();
+        filter.process((input, input), 1000., 0., mode).0.abs()
+      })
+      .skip(2205)
+      .fold(0., f32::max)
+  }
+
+  #[test]
+  fn should_filter_by_mode() {
+    assert!(get_gain(FilterMode::LowPass, 100.) > 0.95);
+    assert!(get_gain(FilterMode::LowPass, 10000.) < 0.05);
+    assert!(get_gain(FilterMode::HighPass, 100.) < 0.05);
+    assert!(get_gain(FilterMode::HighPass, 10000.) > 0.95);
+    assert!(get_gain(FilterMode::BandPass, 1000.) > 0.95);
+    assert!(get_gain(FilterMode::BandPass, 50.) < 0.15);
+    assert!(get_gain(FilterMode::Notch, 1000.) < 0.05);
+    assert!(get_gain(FilterMode::Notch, 10000.) > 0.95);
+  }
+}
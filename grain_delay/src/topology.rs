@@ -0,0 +1,20 @@
+/// Where the grains sit relative to the delay line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+  /// The grains process the delay output, so every repeat is granulated again.
+  InLoop,
+  /// The repeats stay clean and the grains only process the output of the delay.
+  PostDelay,
+  /// The grains process the input, so the delay repeats a single granular layer.
+  PreDelay,
+}
+
+impl From<i32> for Topology {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::PostDelay,
+      2 => Self::PreDelay,
+      _ => Self::InLoop,
+    }
+  }
+}
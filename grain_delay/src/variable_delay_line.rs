@@ -1,35 +1,123 @@
 mod ramp;
 use crate::shared::{
-  delay_line::{DelayLine, Interpolation},
-  float_ext::FloatExt,
+  delay_line::Interpolation, float_ext::FloatExt, freeze::Freeze,
+  stereo_delay_line::StereoDelayLine,
 };
 use ramp::Ramp;
 use std::f32::consts::FRAC_PI_2;
 
+/// How the read position follows a change of the delay time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeMode {
+  Crossfade,
+  Tape,
+  Jump,
+}
+
+impl From<i32> for TimeMode {
+  fn from(value: i32) -> Self {
+    match value {
+      1 => Self::Tape,
+      2 => Self::Jump,
+      _ => Self::Crossfade,
+    }
+  }
+}
+
 pub struct VariableDelayLine {
-  delay_line: DelayLine,
+  delay_line: StereoDelayLine,
   ramp: Ramp,
   previous_time: f32,
   next_time: f32,
+  tape_time: f32,
+  fade_time: f32,
+  glide_coeff: f32,
+  sample_rate: f32,
+  freeze: Freeze,
 }
 
 impl VariableDelayLine {
   pub fn new(length: usize, sample_rate: f32) -> Self {
     Self {
-      delay_line: DelayLine::new(length, sample_rate),
+      delay_line: StereoDelayLine::new(length, sample_rate),
       ramp: Ramp::new(sample_rate, 5.),
       previous_time: 0.,
       next_time: 0.,
+      tape_time: 0.,
+      fade_time: 0.,
+      glide_coeff: 0.,
+      sample_rate,
+      freeze: Freeze::new(sample_rate),
+    }
+  }
+
+  /// Clears the delay lines and adapts to the sample rate without reallocating.
+  pub fn reset(&mut self, sample_rate: f32) {
+    self.delay_line.set_sample_rate(sample_rate);
+    self.delay_line.reset();
+    self.ramp = Ramp::new(sample_rate, 5.);
+    self.previous_time = 0.;
+    self.next_time = 0.;
+    self.tape_time = 0.;
+    self.fade_time = 0.;
+    self.sample_rate = sample_rate;
+    self.freeze = Freeze::new(sample_rate);
+  }
+
+  /// The fade time in milliseconds is the duration of a crossfade. In tape mode the read position
+  /// glides to the new time in about the fade time, which bends the pitch on the way.
+  pub fn read(
+    &mut self,
+    time: f32,
+    mode: TimeMode,
+    fade_time: f32,
+    interp: Interpolation,
+  ) -> (f32, f32) {
+    if fade_time != self.fade_time {
+      self.set_fade_time(fade_time);
+    }
+    match mode {
+      TimeMode::Crossfade => {
+        self.tape_time = time;
+        self.read_crossfaded(time, interp)
+      }
+      TimeMode::Tape => {
+        self.tape_time = time.mix(self.tape_time, self.glide_coeff);
+        self.next_time = self.tape_time;
+        // The read position moves in fractions of a sample, which steps would turn into zipper noise
+        let interp = match interp {
+          Interpolation::Step => Interpolation::Linear,
+          _ => interp,
+        };
+        self.read_at(self.tape_time, interp)
+      }
+      TimeMode::Jump => {
+        self.tape_time = time;
+        self.next_time = time;
+        self.read_at(time, interp)
+      }
+    }
+  }
+
+  pub fn write(&mut self, value: (f32, f32), freeze: bool) {
+    if let Some(gain) = self.freeze.process(freeze) {
+      self.delay_line.write((value.0 * gain, value.1 * gain));
     }
   }
 
-  pub fn read(&mut self, time: f32, interp: Interpolation) -> f32 {
+  fn set_fade_time(&mut self, fade_time: f32) {
+    self.fade_time = fade_time;
+    self.ramp.set_freq(1000. / fade_time);
+    // The glide covers about 99% of the distance in the fade time
+    self.glide_coeff = (-5. / (fade_time * 0.001 * self.sample_rate)).exp();
+  }
+
+  fn read_crossfaded(&mut self, time: f32, interp: Interpolation) -> (f32, f32) {
     let time_has_changed = time != self.next_time;
     match (time_has_changed, self.ramp.is_finished()) {
       (false, true) => {
-        let delay_out = self.delay_line.read(time, interp);
         self.next_time = time;
-        delay_out
+        self.read_at(time, interp)
       }
       (true, true) => {
         self.previous_time = self.next_time;
@@ -41,15 +129,57 @@ impl VariableDelayLine {
     }
   }
 
-  pub fn write(&mut self, value: f32) {
-    self.delay_line.write(value);
+  fn read_at(&self, time: f32, interp: Interpolation) -> (f32, f32) {
+    self.delay_line.read(time, interp)
   }
 
-  fn crossfade(&mut self, interp: Interpolation) -> f32 {
+  fn crossfade(&mut self, interp: Interpolation) -> (f32, f32) {
     let ramp = self.ramp.process();
     let window = (ramp * FRAC_PI_2).fast_cos();
     let window = window * window;
-    self.delay_line.read(self.previous_time, interp) * window
-      + self.delay_line.read(self.next_time, interp) * (1. - window)
+    let previous = self.delay_line.read(self.previous_time, interp);
+    let next = self.delay_line.read(self.next_time, interp);
+    (
+      previous.0 * window + next.0 * (1. - window),
+      previous.1 * window + next.1 * (1. - window),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{TimeMode, VariableDelayLine};
+  use crate::shared::delay_line::Interpolation;
+
+  fn delay_line_with_ramp() -> VariableDelayLine {
+    let mut delay_line = VariableDelayLine::new(64, 1000.);
+    (0..64).for_each(|i| delay_line.write((i as f32, i as f32), false));
+    delay_line
+  }
+
+  #[test]
+  fn should_jump_to_a_new_time() {
+    let mut delay_line = delay_line_with_ramp();
+    let first = delay_line.read(10., TimeMode::Jump, 200., Interpolation::Linear);
+    let second = delay_line.read(20., TimeMode::Jump, 200., Interpolation::Linear);
+    assert_eq!(first.0 - second.0, 10.);
+  }
+
+  #[test]
+  fn should_glide_to_a_new_time_in_tape_mode() {
+    let mut delay_line = delay_line_with_ramp();
+    let at_twenty = delay_line.read(20., TimeMode::Jump, 10., Interpolation::Linear);
+    let at_ten = delay_line.read(10., TimeMode::Jump, 10., Interpolation::Linear);
+    (0..100).for_each(|_| {
+      delay_line.read(10., TimeMode::Tape, 10., Interpolation::Linear);
+    });
+
+    let gliding = delay_line.read(20., TimeMode::Tape, 10., Interpolation::Linear);
+    assert!(gliding.0 < at_ten.0 && gliding.0 > at_twenty.0);
+    let settled = (0..20)
+      .map(|_| delay_line.read(20., TimeMode::Tape, 10., Interpolation::Linear))
+      .last()
+      .unwrap();
+    assert!((settled.0 - at_twenty.0).abs() < 0.1);
   }
 }
@@ -2,6 +2,7 @@ pub struct Ramp {
   x: f32,
   trigger: bool,
   is_active: bool,
+  sample_period: f32,
   step_size: f32,
 }
 
@@ -11,10 +12,15 @@ impl Ramp {
       x: 0.,
       trigger: false,
       is_active: false,
+      sample_period: sample_rate.recip(),
       step_size: sample_rate.recip() * freq,
     }
   }
 
+  pub fn set_freq(&mut self, freq: f32) {
+    self.step_size = self.sample_period * freq;
+  }
+
   pub fn start(&mut self) {
     self.trigger = true;
     self.is_active = true;
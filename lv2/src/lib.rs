@@ -1,73 +1,373 @@
 extern crate grain_delay;
 extern crate lv2;
-use grain_delay::{GrainDelay, Params};
+use grain_delay::{
+  GrainDelay, GrainDelayConfig, Oversampling, ParamValues, Params, MAX_DELAY_TIME, PARAM_COUNT,
+};
 use lv2::prelude::*;
+use std::cell::Cell;
 
-#[derive(PortCollection)]
-struct Ports {
-  spray: InputPort<InPlaceControl>,
-  frequency: InputPort<InPlaceControl>,
-  pitch: InputPort<InPlaceControl>,
-  drift: InputPort<InPlaceControl>,
-  reverse: InputPort<InPlaceControl>,
-  time: InputPort<InPlaceControl>,
-  feedback: InputPort<InPlaceControl>,
-  filter: InputPort<InPlaceControl>,
-  spread: InputPort<InPlaceControl>,
-  mix: InputPort<InPlaceControl>,
+// The default build allocates for 5 seconds of delay. Builds with the `long-delay` feature
+// allocate for the full 30 seconds, which takes about six times the memory.
+#[cfg(not(feature = "long-delay"))]
+const MAX_DELAY: f32 = 5.;
+#[cfg(feature = "long-delay")]
+const MAX_DELAY: f32 = MAX_DELAY_TIME;
+// Builds with the `oversampling` feature run the grains at twice the sample rate, which adds
+// latency that is reported to the host.
+#[cfg(not(feature = "oversampling"))]
+const OVERSAMPLING: Oversampling = Oversampling::Off;
+#[cfg(feature = "oversampling")]
+const OVERSAMPLING: Oversampling = Oversampling::Times2;
+// The sample rate is fixed for an instance, so the buffers are only sized for that sample rate.
+const CONFIG: GrainDelayConfig = GrainDelayConfig {
+  max_delay_time: MAX_DELAY,
+  max_pitch: 24.,
+  max_spray: 0.5,
+  min_frequency: 1.,
+  oversampling: OVERSAMPLING,
+  max_sample_rate: 0.,
+};
+
+// Both plugins share the same control ports and only differ in their audio ports. The audio ports
+// follow the controls of the first version, so the mono plugin keeps the port indices of existing
+// presets and the new controls are appended after them.
+macro_rules! ports {
+  ($name:ident { $($audio_port:ident: $port_type:ty),* $(,)? }) => {
+    #[derive(PortCollection)]
+    struct $name {
+      spray: InputPort<InPlaceControl>,
+      frequency: InputPort<InPlaceControl>,
+      pitch: InputPort<InPlaceControl>,
+      drift: InputPort<InPlaceControl>,
+      reverse: InputPort<InPlaceControl>,
+      time: InputPort<InPlaceControl>,
+      feedback: InputPort<InPlaceControl>,
+      filter: InputPort<InPlaceControl>,
+      spread: InputPort<InPlaceControl>,
+      mix: InputPort<InPlaceControl>,
+      $($audio_port: $port_type,)*
+      voices: InputPort<InPlaceControl>,
+      window_shape: InputPort<InPlaceControl>,
+      taper: InputPort<InPlaceControl>,
+      feedback_mode: InputPort<InPlaceControl>,
+      cross_feedback: InputPort<InPlaceControl>,
+      freeze: InputPort<InPlaceControl>,
+      time_division: InputPort<InPlaceControl>,
+      frequency_division: InputPort<InPlaceControl>,
+      filter_mode: InputPort<InPlaceControl>,
+      resonance: InputPort<InPlaceControl>,
+      saturation: InputPort<InPlaceControl>,
+      drive: InputPort<InPlaceControl>,
+      duck_threshold: InputPort<InPlaceControl>,
+      duck_amount: InputPort<InPlaceControl>,
+      duck_attack: InputPort<InPlaceControl>,
+      duck_release: InputPort<InPlaceControl>,
+      lfo1_shape: InputPort<InPlaceControl>,
+      lfo1_rate: InputPort<InPlaceControl>,
+      lfo1_sync: InputPort<InPlaceControl>,
+      lfo1_depth: InputPort<InPlaceControl>,
+      lfo1_destination: InputPort<InPlaceControl>,
+      lfo2_shape: InputPort<InPlaceControl>,
+      lfo2_rate: InputPort<InPlaceControl>,
+      lfo2_sync: InputPort<InPlaceControl>,
+      lfo2_depth: InputPort<InPlaceControl>,
+      lfo2_destination: InputPort<InPlaceControl>,
+      envelope_attack: InputPort<InPlaceControl>,
+      envelope_release: InputPort<InPlaceControl>,
+      envelope_depth: InputPort<InPlaceControl>,
+      envelope_destination: InputPort<InPlaceControl>,
+      topology: InputPort<InPlaceControl>,
+      diffusion: InputPort<InPlaceControl>,
+      spray_distribution: InputPort<InPlaceControl>,
+      pan_mode: InputPort<InPlaceControl>,
+      interpolation_quality: InputPort<InPlaceControl>,
+      time_mode: InputPort<InPlaceControl>,
+      time_fade: InputPort<InPlaceControl>,
+      latency: OutputPort<InPlaceControl>,
+      control: InputPort<AtomPort>,
+    }
+
+    impl $name {
+      fn values(&self) -> [f32; PARAM_COUNT] {
+        [
+          self.spray.get(),
+          self.frequency.get(),
+          self.pitch.get(),
+          self.drift.get(),
+          self.reverse.get(),
+          self.time.get(),
+          self.feedback.get(),
+          self.filter.get(),
+          self.spread.get(),
+          self.mix.get(),
+          self.voices.get(),
+          self.window_shape.get(),
+          self.taper.get(),
+          self.feedback_mode.get(),
+          self.cross_feedback.get(),
+          self.freeze.get(),
+          self.time_division.get(),
+          self.frequency_division.get(),
+          self.filter_mode.get(),
+          self.resonance.get(),
+          self.saturation.get(),
+          self.drive.get(),
+          self.duck_threshold.get(),
+          self.duck_amount.get(),
+          self.duck_attack.get(),
+          self.duck_release.get(),
+          self.lfo1_shape.get(),
+          self.lfo1_rate.get(),
+          self.lfo1_sync.get(),
+          self.lfo1_depth.get(),
+          self.lfo1_destination.get(),
+          self.lfo2_shape.get(),
+          self.lfo2_rate.get(),
+          self.lfo2_sync.get(),
+          self.lfo2_depth.get(),
+          self.lfo2_destination.get(),
+          self.envelope_attack.get(),
+          self.envelope_release.get(),
+          self.envelope_depth.get(),
+          self.envelope_destination.get(),
+          self.topology.get(),
+          self.diffusion.get(),
+          self.spray_distribution.get(),
+          self.pan_mode.get(),
+          self.interpolation_quality.get(),
+          self.time_mode.get(),
+          self.time_fade.get(),
+        ]
+      }
+    }
+  };
+}
+
+#[derive(FeatureCollection)]
+pub struct Features<'a> {
+  map: LV2Map<'a>,
+}
+
+#[derive(URIDCollection)]
+pub struct URIDs {
+  atom: AtomURIDCollection,
+  unit: UnitURIDCollection,
+  time: TimeURIDCollection,
+}
+
+ports!(Ports {
   input: InputPort<InPlaceAudio>,
   output_left: OutputPort<InPlaceAudio>,
   output_right: OutputPort<InPlaceAudio>,
+});
+
+ports!(StereoPorts {
+  input_left: InputPort<InPlaceAudio>,
+  input_right: InputPort<InPlaceAudio>,
+  output_left: OutputPort<InPlaceAudio>,
+  output_right: OutputPort<InPlaceAudio>,
+});
+
+// The audio ports may point to the same buffer, so the input is copied in chunks before the
+// output is written.
+const CHUNK_SIZE: usize = 64;
+
+fn read_chunk(ports: &[Cell<f32>], chunk: &mut [f32; CHUNK_SIZE]) -> usize {
+  ports
+    .iter()
+    .zip(chunk.iter_mut())
+    .for_each(|(port, sample)| *sample = port.get());
+  ports.len()
+}
+
+fn write_chunk(ports: &[Cell<f32>], chunk: &[f32]) {
+  ports
+    .iter()
+    .zip(chunk)
+    .for_each(|(port, sample)| port.set(*sample));
+}
+
+// The parameters are read from the control ports, so the atom port only carries the time position.
+// Returns the last tempo of this cycle.
+fn read_tempo(control: &InputPort<AtomPort>, urids: &URIDs) -> Option<f32> {
+  let sequence = control.read(urids.atom.sequence, urids.unit.beat)?;
+  let mut tempo = None;
+  for (_, atom) in sequence {
+    let Some((header, object)) = atom.read(urids.atom.object, ()) else {
+      continue;
+    };
+    if header.otype == urids.time.position_class {
+      for (property_header, property) in object {
+        if property_header.key == urids.time.beats_per_minute {
+          tempo = property.read(urids.atom.float, ()).or(tempo);
+        }
+      }
+    }
+  }
+  tempo
 }
 
 #[uri("https://github.com/davemollen/dm-GrainDelay")]
 struct DmGrainDelay {
   grain_delay: GrainDelay,
   params: Params,
+  urids: URIDs,
+  tempo: f32,
+}
+
+impl DmGrainDelay {
+  // Reads the tempo and the control port values of this cycle.
+  fn prepare(&mut self, control: &InputPort<AtomPort>, values: [f32; PARAM_COUNT]) {
+    if let Some(tempo) = read_tempo(control, &self.urids) {
+      self.tempo = tempo;
+    }
+    self
+      .params
+      .set(&ParamValues::from_values(&values, self.tempo));
+  }
 }
 
 impl Plugin for DmGrainDelay {
   // Tell the framework which ports this plugin has.
   type Ports = Ports;
 
-  // We don't need any special host features; We can leave them out.
-  type InitFeatures = ();
+  // The URID map is needed to read the time position from the host.
+  type InitFeatures = Features<'static>;
   type AudioFeatures = ();
 
-  // Create a new instance of the plugin; Trivial in this case.
-  fn new(plugin_info: &PluginInfo, _features: &mut ()) -> Option<Self> {
+  // Create a new instance of the plugin.
+  fn new(plugin_info: &PluginInfo, features: &mut Features<'static>) -> Option<Self> {
     let sample_rate = plugin_info.sample_rate() as f32;
 
     Some(Self {
-      grain_delay: GrainDelay::new(sample_rate),
+      grain_delay: GrainDelay::with_config(sample_rate, CONFIG),
       params: Params::new(sample_rate),
+      urids: features.map.populate_collection()?,
+      tempo: 120.,
     })
   }
 
   // Process a chunk of audio. The audio ports are dereferenced to slices, which the plugin
   // iterates over.
   fn run(&mut self, ports: &mut Ports, _features: &mut (), _sample_count: u32) {
-    self.params.set(
-      ports.spray.get(),
-      ports.frequency.get(),
-      ports.pitch.get(),
-      ports.drift.get() * 0.01,
-      ports.reverse.get() * 0.01,
-      ports.time.get(),
-      ports.feedback.get() * 0.01,
-      ports.filter.get(),
-      ports.spread.get() * 0.01,
-      ports.mix.get() * 0.01,
-    );
-
-    let output_channels = ports.output_left.iter().zip(ports.output_right.iter());
-    for (input, (output_left, output_right)) in ports.input.iter().zip(output_channels) {
-      let output = self.grain_delay.process(input.get(), &mut self.params);
-      output_left.set(output.0);
-      output_right.set(output.1);
+    self.prepare(&ports.control, ports.values());
+    ports.latency.set(self.grain_delay.latency() as f32);
+
+    let mut input = [0.; CHUNK_SIZE];
+    let mut left = [0.; CHUNK_SIZE];
+    let mut right = [0.; CHUNK_SIZE];
+    for ((input_ports, left_ports), right_ports) in ports
+      .input
+      .chunks(CHUNK_SIZE)
+      .zip(ports.output_left.chunks(CHUNK_SIZE))
+      .zip(ports.output_right.chunks(CHUNK_SIZE))
+    {
+      let length = read_chunk(input_ports, &mut input);
+      self.grain_delay.process_block(
+        &input[..length],
+        &mut left[..length],
+        &mut right[..length],
+        &mut self.params,
+      );
+      write_chunk(left_ports, &left);
+      write_chunk(right_ports, &right);
+    }
+  }
+}
+
+#[uri("https://github.com/davemollen/dm-GrainDelay#stereo")]
+struct DmGrainDelayStereo(DmGrainDelay);
+
+impl Plugin for DmGrainDelayStereo {
+  type Ports = StereoPorts;
+  type InitFeatures = Features<'static>;
+  type AudioFeatures = ();
+
+  fn new(plugin_info: &PluginInfo, features: &mut Features<'static>) -> Option<Self> {
+    DmGrainDelay::new(plugin_info, features).map(Self)
+  }
+
+  fn run(&mut self, ports: &mut StereoPorts, _features: &mut (), _sample_count: u32) {
+    let plugin = &mut self.0;
+    plugin.prepare(&ports.control, ports.values());
+    ports.latency.set(plugin.grain_delay.latency() as f32);
+
+    let mut input_left = [0.; CHUNK_SIZE];
+    let mut input_right = [0.; CHUNK_SIZE];
+    let mut left = [0.; CHUNK_SIZE];
+    let mut right = [0.; CHUNK_SIZE];
+    for (((input_left_ports, input_right_ports), left_ports), right_ports) in ports
+      .input_left
+      .chunks(CHUNK_SIZE)
+      .zip(ports.input_right.chunks(CHUNK_SIZE))
+      .zip(ports.output_left.chunks(CHUNK_SIZE))
+      .zip(ports.output_right.chunks(CHUNK_SIZE))
+    {
+      let length = read_chunk(input_left_ports, &mut input_left);
+      read_chunk(input_right_ports, &mut input_right);
+      plugin.grain_delay.process_block_stereo(
+        &input_left[..length],
+        &input_right[..length],
+        &mut left[..length],
+        &mut right[..length],
+        &mut plugin.params,
+      );
+      write_chunk(left_ports, &left);
+      write_chunk(right_ports, &right);
     }
   }
 }
 
 // Generate the plugin descriptor function which exports the plugin to the outside world.
-lv2_descriptors!(DmGrainDelay);
+lv2_descriptors!(DmGrainDelay, DmGrainDelayStereo);
+
+#[cfg(test)]
+mod tests {
+  use grain_delay::PARAMS;
+
+  const FIRST_VERSION_PARAM_COUNT: usize = 10;
+
+  // The LV2 ports are declared in static files, so they're checked against the table.
+  #[test]
+  fn should_match_the_lv2_ports() {
+    for (ttl, audio_port_count) in [
+      (
+        include_str!("../dm-GrainDelay.lv2/dm-GrainDelay_dsp.ttl"),
+        3,
+      ),
+      (
+        include_str!("../dm-GrainDelay.lv2/dm-GrainDelayStereo_dsp.ttl"),
+        4,
+      ),
+    ] {
+      for (index, descriptor) in PARAMS.iter().enumerate() {
+        let index = if index < FIRST_VERSION_PARAM_COUNT {
+          index
+        } else {
+          index + audio_port_count
+        };
+        let symbol = format!("lv2:symbol \"{}\"", descriptor.symbol);
+        let port = ttl
+          .split("lv2:index ")
+          .find(|port| port.contains(&symbol))
+          .unwrap_or_else(|| panic!("missing port {}", descriptor.symbol));
+        assert_eq!(
+          port.split(|c: char| !c.is_ascii_digit()).next(),
+          Some(index.to_string().as_str())
+        );
+        let property = |name: &str| -> f32 {
+          let start = port.find(name).unwrap() + name.len();
+          port[start..]
+            .split(|c: char| c.is_whitespace() || c == ';')
+            .find(|value| !value.is_empty())
+            .unwrap()
+            .parse()
+            .unwrap()
+        };
+        assert_eq!(property("lv2:default"), descriptor.default);
+        assert_eq!(property("lv2:minimum"), descriptor.min);
+        assert_eq!(property("lv2:maximum"), descriptor.max);
+      }
+    }
+  }
+}
@@ -1,5 +1,5 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use grain_delay::{GrainDelay, Params};
+use grain_delay::{GrainDelay, ParamValues, Params};
 
 fn generate_signal() -> f32 {
   fastrand::f32() * 2. - 1.
@@ -12,8 +12,22 @@ fn generate_signal_stream(length: usize) -> Vec<f32> {
 fn grain_delay_bench(c: &mut Criterion) {
   let mut grain_delay = GrainDelay::new(44100.);
   let mut params = Params::new(44100.);
-  params.set(2., 7., 12., 0.2, 0.5, 200., 0.8, 4000., 0.5, 0.5);
+  params.set(&ParamValues {
+    spray: 2.,
+    frequency: 7.,
+    pitch: 12.,
+    drift: 20.,
+    reverse: 50.,
+    time: 200.,
+    feedback: 80.,
+    filter: 4000.,
+    spread: 50.,
+    mix: 50.,
+    ..Default::default()
+  });
   let signal_stream = generate_signal_stream(44100);
+  let mut left = vec![0.; signal_stream.len()];
+  let mut right = vec![0.; signal_stream.len()];
 
   c.bench_function("grain_delay", |b| {
     b.iter(|| {
@@ -22,6 +36,18 @@ fn grain_delay_bench(c: &mut Criterion) {
       }
     })
   });
+
+  c.bench_function("grain_delay_block", |b| {
+    b.iter(|| {
+      for ((input, left), right) in signal_stream
+        .chunks(128)
+        .zip(left.chunks_mut(128))
+        .zip(right.chunks_mut(128))
+      {
+        grain_delay.process_block(input, left, right, &mut params);
+      }
+    })
+  });
 }
 
 criterion_group!(benches, grain_delay_bench);
@@ -1,4 +1,4 @@
-use grain_delay::{GrainDelay, Params};
+use grain_delay::{GrainDelay, ParamValues, Params};
 
 fn generate_signal() -> f32 {
   fastrand::f32() * 2. - 1.
@@ -7,7 +7,19 @@ fn generate_signal() -> f32 {
 fn main() {
   let mut grain_delay = GrainDelay::new(44100.);
   let mut params = Params::new(44100.);
-  params.set(2., 7., 12., 0.2, 0.5, 200., 0.8, 4000., 0.5, 0.5);
+  params.set(&ParamValues {
+    spray: 2.,
+    frequency: 7.,
+    pitch: 12.,
+    drift: 20.,
+    reverse: 50.,
+    time: 200.,
+    feedback: 80.,
+    filter: 4000.,
+    spread: 50.,
+    mix: 50.,
+    ..Default::default()
+  });
 
   loop {
     let input = generate_signal();
@@ -1,32 +1,69 @@
 #[macro_use]
 extern crate vst;
 mod grain_delay_parameters;
-use grain_delay::{GrainDelay, Params as ProcessParams};
+mod nrpn;
+use grain_delay::{
+  GrainDelay, GrainDelayConfig, Oversampling, ParamEvent, ParamValues, Params as ProcessParams,
+  MAX_DELAY_TIME, PARAM_COUNT,
+};
 use grain_delay_parameters::GrainDelayParameters;
+use nrpn::Nrpn;
 use std::sync::Arc;
 use vst::{
+  api::{Events, Supported, TimeInfoFlags},
   buffer::AudioBuffer,
-  plugin::{Category, Info, Plugin, PluginParameters},
+  event::Event,
+  host::Host,
+  plugin::{CanDo, Category, Info, Plugin, PluginParameters},
   prelude::HostCallback,
 };
 
+const MAX_EVENTS: usize = 512;
+// The default build allocates for 5 seconds of delay. Builds with the `long-delay` feature
+// allocate for the full 30 seconds, which takes about six times the memory.
+#[cfg(not(feature = "long-delay"))]
+const MAX_DELAY: f32 = 5.;
+#[cfg(feature = "long-delay")]
+const MAX_DELAY: f32 = MAX_DELAY_TIME;
+// Builds with the `oversampling` feature run the grains at twice the sample rate, which adds
+// latency that the host compensates for.
+#[cfg(not(feature = "oversampling"))]
+const OVERSAMPLING: Oversampling = Oversampling::Off;
+#[cfg(feature = "oversampling")]
+const OVERSAMPLING: Oversampling = Oversampling::Times2;
+const CONFIG: GrainDelayConfig = GrainDelayConfig {
+  max_delay_time: MAX_DELAY,
+  max_pitch: 24.,
+  max_spray: 0.5,
+  min_frequency: 1.,
+  oversampling: OVERSAMPLING,
+  max_sample_rate: 48000.,
+};
+
 struct DmGrainDelay {
+  host: HostCallback,
   params: Arc<GrainDelayParameters>,
   grain_delay: GrainDelay,
   process_params: ProcessParams,
+  events: Vec<ParamEvent>,
+  nrpn: Nrpn,
 }
 
 impl Plugin for DmGrainDelay {
-  fn new(_: HostCallback) -> Self {
+  fn new(host: HostCallback) -> Self {
     Self {
+      host,
       params: Arc::new(GrainDelayParameters::default()),
-      grain_delay: GrainDelay::new(44100.),
+      grain_delay: GrainDelay::with_config(44100., CONFIG),
       process_params: ProcessParams::new(44100.),
+      events: Vec::with_capacity(MAX_EVENTS),
+      nrpn: Nrpn::default(),
     }
   }
 
   fn set_sample_rate(&mut self, sample_rate: f32) {
-    self.grain_delay = GrainDelay::new(sample_rate);
+    self.grain_delay.set_sample_rate(sample_rate);
+    self.process_params.set_sample_rate(sample_rate);
   }
 
   fn get_info(&self) -> Info {
@@ -34,9 +71,11 @@ impl Plugin for DmGrainDelay {
       name: "dm-GrainDelay".to_string(),
       vendor: "DM".to_string(),
       version: 1,
-      inputs: 1,
+      inputs: 2,
       outputs: 2,
-      parameters: 10,
+      parameters: PARAM_COUNT as i32,
+      midi_inputs: 1,
+      initial_delay: self.grain_delay.latency() as i32,
       unique_id: 1358,
       f64_precision: true,
       category: Category::Effect,
@@ -45,27 +84,58 @@ impl Plugin for DmGrainDelay {
   }
 
   fn process(&mut self, buffer: &mut AudioBuffer<f32>) {
-    self.process_params.set(
-      self.params.spray.get(),
-      self.params.frequency.get(),
-      self.params.pitch.get(),
-      self.params.drift.get(),
-      self.params.reverse.get(),
-      self.params.time.get(),
-      self.params.feedback.get(),
-      self.params.filter.get(),
-      self.params.spread.get(),
-      self.params.mix.get(),
+    let tempo = self.get_tempo();
+    let params = &self.params;
+    let host = &self.host;
+    self
+      .process_params
+      .set(&ParamValues::from_values(&params.values(), tempo));
+
+    let (input_channels, output_channels) = buffer.split();
+    let (mut left_channel, mut right_channel) = output_channels.split_at_mut(1);
+    self.grain_delay.process_block_stereo_with_events(
+      (input_channels.get(0), input_channels.get(1)),
+      left_channel.get_mut(0),
+      right_channel.get_mut(0),
+      &mut self.process_params,
+      &self.events,
+      |event, process_params| {
+        params.set_parameter(event.index as i32, event.value);
+        host.automate(event.index as i32, event.value);
+        process_params.set(&ParamValues::from_values(&params.values(), tempo));
+      },
     );
+    self.events.clear();
+  }
+
+  // Every parameter can be automated with NRPN messages, where the NRPN number is the parameter
+  // index. The changes are applied at their sample offset in the next process call and reported to
+  // the host, so it can record them and update its controls.
+  fn process_events(&mut self, events: &Events) {
+    for event in events.events() {
+      if let Event::Midi(midi_event) = event {
+        let [status, controller, value] = midi_event.data;
+        if status & 0xf0 != 0xb0 {
+          continue;
+        }
+        match self.nrpn.process(controller, value) {
+          Some((index, value)) if index < PARAM_COUNT && self.events.len() < MAX_EVENTS => {
+            self.events.push(ParamEvent::new(
+              midi_event.delta_frames.max(0) as usize,
+              index,
+              value,
+            ))
+          }
+          _ => (),
+        }
+      }
+    }
+  }
 
-    let (input_channels, mut output_channels) = buffer.split();
-    let input = input_channels.get(0);
-    let zipped_output_channels = output_channels
-      .get_mut(0)
-      .iter_mut()
-      .zip(output_channels.get_mut(1).iter_mut());
-    for (input, (output_left, output_right)) in input.iter().zip(zipped_output_channels) {
-      (*output_left, *output_right) = self.grain_delay.process(*input, &mut self.process_params);
+  fn can_do(&self, can_do: CanDo) -> Supported {
+    match can_do {
+      CanDo::ReceiveEvents | CanDo::ReceiveMidiEvent => Supported::Yes,
+      _ => Supported::Maybe,
     }
   }
 
@@ -74,4 +144,14 @@ impl Plugin for DmGrainDelay {
   }
 }
 
+impl DmGrainDelay {
+  fn get_tempo(&self) -> f32 {
+    let tempo_valid = TimeInfoFlags::TEMPO_VALID.bits();
+    match self.host.get_time_info(tempo_valid) {
+      Some(time_info) if time_info.flags & tempo_valid != 0 => time_info.tempo as f32,
+      _ => 120.,
+    }
+  }
+}
+
 plugin_main!(DmGrainDelay);
@@ -0,0 +1,42 @@
+const PARAMETER_MSB: u8 = 99;
+const PARAMETER_LSB: u8 = 98;
+const DATA_ENTRY_MSB: u8 = 6;
+const DATA_ENTRY_LSB: u8 = 38;
+const MAX_VALUE: f32 = 16383.;
+
+/// Reads NRPN controller changes, which select a parameter by its index and send it a 14-bit value.
+#[derive(Default)]
+pub struct Nrpn {
+  parameter: (u8, u8),
+  value_msb: u8,
+}
+
+impl Nrpn {
+  /// Returns the parameter index and the normalized value when the least significant data entry
+  /// byte arrives. The most significant byte is held until then, so each pair is a single change.
+  pub fn process(&mut self, controller: u8, value: u8) -> Option<(usize, f32)> {
+    let value = value & 0x7f;
+    match controller {
+      PARAMETER_MSB => {
+        self.parameter.0 = value;
+        None
+      }
+      PARAMETER_LSB => {
+        self.parameter.1 = value;
+        None
+      }
+      DATA_ENTRY_MSB => {
+        self.value_msb = value;
+        None
+      }
+      DATA_ENTRY_LSB => Some(self.get_parameter_change(value)),
+      _ => None,
+    }
+  }
+
+  fn get_parameter_change(&self, value_lsb: u8) -> (usize, f32) {
+    let index = ((self.parameter.0 as usize) << 7) | self.parameter.1 as usize;
+    let value = (((self.value_msb as u16) << 7) | value_lsb as u16) as f32 / MAX_VALUE;
+    (index, value)
+  }
+}